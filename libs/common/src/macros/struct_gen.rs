@@ -88,6 +88,7 @@ macro_rules! struct_gen {
 
     impl$(<$($generic_param$(: $generic_constraint)?),*>)? $struct_name$(<$($generic_param),*>)? {
       #[allow(dead_code)]
+      #[allow(clippy::too_many_arguments)]
       pub fn new($($field_name: $field_type),*) -> Self {
         Self {
           $( $field_name ),*
@@ -14,7 +14,7 @@ use std_v2::{
 };
 mod operations;
 use operations::{
-  completions::Options as CompletionsCommand, env::Options as EnvCommand, help::Options as HelpCommand, info::Options as InfoCommand, notify::Options as NotifyCommand, run::Options as RunCommand, upgrade::Options as UpgradeCommand,
+  completions::Options as CompletionsCommand, env::Options as EnvCommand, help::Options as HelpCommand, history::Options as HistoryCommand, info::Options as InfoCommand, logs::Options as LogsCommand, notify::Options as NotifyCommand, preset::Options as PresetCommand, ps::Options as PsCommand, restart::Options as RestartCommand, run::Options as RunCommand, stop::Options as StopCommand, upgrade::Options as UpgradeCommand,
   version::Options as VersionCommand,
 };
 
@@ -52,6 +52,18 @@ pub enum Commands {
   Upgrade(UpgradeCommand),
   #[operation("Run a command")]
   Run(RunCommand),
+  #[operation("Manage run presets")]
+  Preset(PresetCommand),
+  #[operation("List daemonized jobs")]
+  Ps(PsCommand),
+  #[operation("Stop a daemonized job")]
+  Stop(StopCommand),
+  #[operation("Restart a daemonized job")]
+  Restart(RestartCommand),
+  #[operation("Show the output of a daemonized job")]
+  Logs(LogsCommand),
+  #[operation("Show and replay previous runs")]
+  History(HistoryCommand),
   #[operation("Show information about the system")]
   Info(InfoCommand),
   #[operation("Generate shell completions")]
@@ -121,6 +133,12 @@ struct_gen! {
             Commands::Version(options) => execute_command(options),
             Commands::Upgrade(options) => execute_command(options),
             Commands::Run(options) => execute_command(options),
+            Commands::Preset(options) => execute_command(options),
+            Commands::Ps(options) => execute_command(options),
+            Commands::Stop(options) => execute_command(options),
+            Commands::Restart(options) => execute_command(options),
+            Commands::Logs(options) => execute_command(options),
+            Commands::History(options) => execute_command(options),
             Commands::Info(options) => execute_command(options),
             Commands::Completions(options) => execute_command(options),
             Commands::Notify(options) => execute_command(options),
@@ -233,6 +251,13 @@ fn run(argv: Vec<String>) {
 }
 
 fn main() {
+  let argv = std::env::args().collect::<Vec<String>>();
+
+  // ctr starts itself as the init process of sandboxes, which may not be able to write to the config directory
+  if argv.get(1).is_some_and(|arg| arg == operations::run::sandbox::INIT_ARG) {
+    operations::run::sandbox::init(argv.get(2..).unwrap_or_default());
+  }
+
   (!USER_CONFIG_DIR.exists()).then(|| std::fs::create_dir_all(&*USER_CONFIG_DIR).unwrap());
-  run(std::env::args().collect::<Vec<String>>());
+  run(argv);
 }
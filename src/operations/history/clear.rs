@@ -0,0 +1,28 @@
+use clap::Args;
+use std_v2::{command::Operation, console::CONSOLE, struct_gen};
+
+use crate::operations::run::history::HistoryEntry;
+
+struct_gen! {
+  pub struct Options use Args, std_v2::derive::Command {
+    #[arg(short = 'H', long), help]
+    let help: bool = false;
+  }
+
+  impl Operation {
+    const NAME: &'static str = "clear";
+    const PARENT: Option<&'static str> = Some("history");
+
+    fn main(self: &Self) -> std::io::Result<()> {
+      (self.help).then(|| Self::usage(0));
+
+      let path = HistoryEntry::entries_path();
+      match std::fs::remove_file(&path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+        _ => CONSOLE.print(format!("<brightmagenta>Removed {}</brightmagenta>", path.display())),
+      }
+
+      Ok(())
+    }
+  }
+}
@@ -0,0 +1,29 @@
+use clap::Args;
+use std_v2::{command::Operation, console::CONSOLE, struct_gen};
+
+use crate::operations::run::history::HistorySettings;
+
+struct_gen! {
+  pub struct Options use Args, std_v2::derive::Command {
+    #[arg(short = 'H', long), help]
+    let help: bool = false;
+  }
+
+  impl Operation {
+    const NAME: &'static str = "disable";
+    const PARENT: Option<&'static str> = Some("history");
+
+    fn main(self: &Self) -> std::io::Result<()> {
+      (self.help).then(|| Self::usage(0));
+
+      let mut settings = HistorySettings::load();
+      settings.enabled = false;
+      settings.save()?;
+
+      // recorded runs are kept until `ctr history clear`
+      CONSOLE.print("Runs are no longer recorded");
+
+      Ok(())
+    }
+  }
+}
@@ -0,0 +1,41 @@
+use clap::Args;
+use std_v2::{command::Operation, console::CONSOLE, struct_gen};
+
+use crate::operations::run::{
+  history::{HistoryEntry, HistorySettings},
+  limits::LimitValue,
+};
+
+struct_gen! {
+  pub struct Options use Args, std_v2::derive::Command {
+    #[arg(short = 'H', long), help]
+    let help: bool = false;
+
+    #[arg(short, long), flag("Size after which the oldest runs are dropped", example = "1M")]
+    let max_size: Option<LimitValue> = None;
+  }
+
+  impl Operation {
+    const NAME: &'static str = "enable";
+    const PARENT: Option<&'static str> = Some("history");
+
+    fn main(self: &Self) -> std::io::Result<()> {
+      (self.help).then(|| Self::usage(0));
+
+      let mut settings = HistorySettings::load();
+      settings.enabled = true;
+      if let Some(max_size) = self.max_size {
+        settings.max_size = max_size.0;
+      }
+
+      settings.save()?;
+      CONSOLE.print(format!(
+        "Recording runs in <bold>{}</bold> <brightblack>(at most {} bytes)</brightblack>",
+        HistoryEntry::entries_path().display(),
+        settings.max_size
+      ));
+
+      Ok(())
+    }
+  }
+}
@@ -0,0 +1,81 @@
+use clap::Args;
+use std_v2::{command::Operation, console::CONSOLE, struct_gen};
+
+use super::format_duration;
+use crate::operations::{
+  escape_markup,
+  run::{
+    duration::HumanDuration,
+    history::{HistoryEntry, HistoryFilter, HistorySettings},
+    log::local_time,
+  },
+};
+
+struct_gen! {
+  pub struct Options use Args, std_v2::derive::Command {
+    #[arg(short = 'H', long), help]
+    let help: bool = false;
+
+    #[arg(short, long), flag("Only show runs of the given preset", example = "build")]
+    let preset: Option<String> = None;
+
+    #[arg(short, long), flag("Only show runs that failed")]
+    let failed: bool = false;
+
+    #[arg(short, long), flag("Only show runs started within the given duration", example = "1d")]
+    let since: Option<HumanDuration> = None;
+
+    #[arg(short, long), flag("Only show runs started before the given duration", example = "1h")]
+    let until: Option<HumanDuration> = None;
+  }
+
+  impl Operation {
+    const NAME: &'static str = "list";
+    const PARENT: Option<&'static str> = Some("history");
+
+    fn main(self: &Self) -> std::io::Result<()> {
+      (self.help).then(|| Self::usage(0));
+
+      let filter = HistoryFilter {
+        preset: self.preset.clone(),
+        failed: self.failed,
+        since: self.since.map(|since| since.0),
+        until: self.until.map(|until| until.0),
+      };
+
+      let entries = HistoryEntry::load_all().into_iter().filter(|entry| filter.matches(entry)).collect::<Vec<HistoryEntry>>();
+      if entries.is_empty() {
+        CONSOLE.print("No recorded runs");
+        (!HistorySettings::load().enabled).then(|| CONSOLE.info("The history is disabled, use <magenta>ctr history enable</magenta> to record runs"));
+        return Ok(());
+      }
+
+      let rows = entries.iter()
+        .map(|entry| {
+          let started = i64::try_from(entry.started / 1000).map_or(String::new(), local_time);
+          let duration = entry.duration().map_or("-".to_owned(), format_duration);
+          let exit = match entry.exit_code {
+            Some(0_i32) => "<green>0</green>".to_owned(),
+            Some(code) => format!("<red>{code}</red>"),
+            None => "<brightblack>daemon</brightblack>".to_owned(),
+          };
+
+          (entry.id.to_string(), started, duration, exit, entry.exit_code.map_or(6, |code| code.to_string().len()), escape_markup(&entry.command))
+        })
+        .collect::<Vec<(String, String, String, String, usize, String)>>();
+      let id_len = rows.iter().map(|row| row.0.len()).chain([2]).max().unwrap_or(0);
+      let duration_len = rows.iter().map(|row| row.2.len()).chain([8]).max().unwrap_or(0);
+      let exit_len = rows.iter().map(|row| row.4).chain([4]).max().unwrap_or(0);
+
+      CONSOLE.print(format!("<bold>{:id_len$}    {:19}    {:duration_len$}    {:exit_len$}    COMMAND</bold>", "ID", "STARTED", "DURATION", "EXIT"));
+      for ((id, started, duration, exit, exit_width, command), entry) in rows.into_iter().zip(&entries) {
+        // the markup of the exit code does not count towards the width
+        let padding = " ".repeat(exit_len.saturating_sub(exit_width));
+        let name = entry.preset.as_ref().map_or(String::new(), |preset| format!("<brightmagenta>{}</brightmagenta> ", escape_markup(preset)));
+        CONSOLE.print(format!("<brightblue>{id:id_len$}</brightblue>    {started:19}    {duration:duration_len$}    {exit}{padding}    {name}<brightblack>{command}</brightblack>"));
+      }
+
+      Ok(())
+    }
+  }
+}
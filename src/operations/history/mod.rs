@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use clap::{Args, Subcommand};
+use std_v2::{command::Operation, derive::Command, struct_gen};
+
+use super::ps::Options as PsCommand;
+use crate::execute_command;
+
+pub mod clear;
+pub mod disable;
+pub mod enable;
+pub mod list;
+pub mod rerun;
+pub mod stats;
+
+use clear::Options as ClearCommand;
+use disable::Options as DisableCommand;
+use enable::Options as EnableCommand;
+use list::Options as ListCommand;
+use rerun::Options as RerunCommand;
+use stats::Options as StatsCommand;
+
+#[derive(Debug, Subcommand, Command)]
+#[non_exhaustive]
+pub enum Commands {
+  #[operation("List recorded runs")]
+  List(ListCommand),
+  #[operation("Show statistics per preset")]
+  Stats(StatsCommand),
+  #[operation("Run a recorded command again")]
+  Rerun(RerunCommand),
+  #[operation("Start recording runs")]
+  Enable(EnableCommand),
+  #[operation("Stop recording runs")]
+  Disable(DisableCommand),
+  #[operation("Remove all recorded runs")]
+  Clear(ClearCommand),
+}
+
+impl Operation for Commands {
+  const NAME: &'static str = "history";
+
+  fn main(&self) -> std::io::Result<()> {
+    Ok(())
+  }
+}
+
+/// Like `3m 12s`, or in milliseconds for runs shorter than a second.
+pub fn format_duration(duration: Duration) -> String {
+  if duration < Duration::from_secs(1) {
+    format!("{}ms", duration.as_millis())
+  } else {
+    PsCommand::format_uptime(duration)
+  }
+}
+
+struct_gen! {
+  #[usage(Flags, Operand { name: "operation".to_string() })]
+  pub struct Options use Args, Command {
+    #[command(subcommand)]
+    let command: Option<Commands> = None;
+
+    #[arg(short = 'H', long), help]
+    let help: bool = false;
+  }
+
+  impl Operation {
+    const NAME: &'static str = "history";
+
+    fn main(self: &Self) -> std::io::Result<()> {
+      (self.help).then(|| Self::usage(0));
+
+      match self.command {
+        Some(Commands::List(ref options)) => execute_command(options),
+        Some(Commands::Stats(ref options)) => execute_command(options),
+        Some(Commands::Rerun(ref options)) => execute_command(options),
+        Some(Commands::Enable(ref options)) => execute_command(options),
+        Some(Commands::Disable(ref options)) => execute_command(options),
+        Some(Commands::Clear(ref options)) => execute_command(options),
+        None => Self::usage(0),
+      }
+
+      Ok(())
+    }
+  }
+}
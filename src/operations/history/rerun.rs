@@ -0,0 +1,41 @@
+use std::process::Command;
+
+use clap::Args;
+use std_v2::{command::Operation, console::CONSOLE, struct_gen};
+
+use crate::operations::run::{history::HistoryEntry, signals::exit_code};
+
+struct_gen! {
+  #[usage(Flags, Operand { name: "id".to_string() })]
+  pub struct Options use Args, std_v2::derive::Command {
+    #[arg(short = 'H', long), help]
+    let help: bool = false;
+
+    #[arg()]
+    let id: Option<String> = None;
+  }
+
+  impl Operation {
+    const NAME: &'static str = "rerun";
+    const PARENT: Option<&'static str> = Some("history");
+
+    fn main(self: &Self) -> std::io::Result<()> {
+      (self.help).then(|| Self::usage(0));
+
+      let entry = HistoryEntry::find(&self.id);
+      if !entry.cwd.is_dir() {
+        CONSOLE.exit(format!("The directory <brightmagenta>{}</brightmagenta> of the run does not exist anymore", entry.cwd.display()));
+      }
+
+      // replayed exactly as it was invoked, presets are resolved again though
+      let executable = std::env::current_exe()?;
+      let status = Command::new(executable)
+        .args(entry.argv.iter().skip(1))
+        .current_dir(&entry.cwd)
+        .status()
+        .unwrap_or_else(|err| CONSOLE.exit(format!("Failed to rerun <brightmagenta>{}</brightmagenta>: {err}", entry.id)));
+
+      std::process::exit(exit_code(status));
+    }
+  }
+}
@@ -0,0 +1,97 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use clap::Args;
+use std_v2::{command::Operation, console::CONSOLE, struct_gen};
+
+use super::format_duration;
+use crate::operations::{
+  escape_markup,
+  run::{
+    duration::HumanDuration,
+    history::{HistoryEntry, HistoryFilter},
+  },
+};
+
+struct_gen! {
+  pub struct Options use Args, std_v2::derive::Command {
+    #[arg(short = 'H', long), help]
+    let help: bool = false;
+
+    #[arg(short, long), flag("Only include runs of the given preset", example = "build")]
+    let preset: Option<String> = None;
+
+    #[arg(short, long), flag("Only include runs that failed")]
+    let failed: bool = false;
+
+    #[arg(short, long), flag("Only include runs started within the given duration", example = "1d")]
+    let since: Option<HumanDuration> = None;
+
+    #[arg(short, long), flag("Only include runs started before the given duration", example = "1h")]
+    let until: Option<HumanDuration> = None;
+  }
+
+  impl Operation {
+    const NAME: &'static str = "stats";
+    const PARENT: Option<&'static str> = Some("history");
+
+    fn main(self: &Self) -> std::io::Result<()> {
+      (self.help).then(|| Self::usage(0));
+
+      let filter = HistoryFilter {
+        preset: self.preset.clone(),
+        failed: self.failed,
+        since: self.since.map(|since| since.0),
+        until: self.until.map(|until| until.0),
+      };
+
+      // daemonized runs have no duration or exit code
+      let mut runs: BTreeMap<String, Vec<HistoryEntry>> = BTreeMap::new();
+      for entry in HistoryEntry::load_all().into_iter().filter(|entry| !entry.daemonized && filter.matches(entry)) {
+        runs.entry(entry.name()).or_default().push(entry);
+      }
+
+      if runs.is_empty() {
+        CONSOLE.print("No recorded runs");
+        return Ok(());
+      }
+
+      let rows = runs.iter()
+        .map(|(name, entries)| {
+          let mut durations = entries.iter().filter_map(HistoryEntry::duration).collect::<Vec<Duration>>();
+          durations.sort();
+
+          let failures = entries.iter().filter(|entry| entry.failed()).count();
+          let rate = failures.saturating_mul(100).checked_div(entries.len()).unwrap_or(0);
+
+          (
+            escape_markup(name),
+            entries.len().to_string(),
+            Self::percentile(&durations, 50).map_or("-".to_owned(), format_duration),
+            Self::percentile(&durations, 95).map_or("-".to_owned(), format_duration),
+            format!("{rate}%"),
+          )
+        })
+        .collect::<Vec<(String, String, String, String, String)>>();
+      let name_len = runs.keys().map(String::len).chain([6]).max().unwrap_or(0);
+      let count_len = rows.iter().map(|row| row.1.len()).chain([5]).max().unwrap_or(0);
+      let p50_len = rows.iter().map(|row| row.2.len()).chain([3]).max().unwrap_or(0);
+      let p95_len = rows.iter().map(|row| row.3.len()).chain([3]).max().unwrap_or(0);
+
+      CONSOLE.print(format!("<bold>{:name_len$}    {:count_len$}    {:p50_len$}    {:p95_len$}    FAILURES</bold>", "PRESET", "COUNT", "P50", "P95"));
+      for ((name, count, p50, p95, rate), raw_name) in rows.into_iter().zip(runs.keys()) {
+        let padding = " ".repeat(name_len.saturating_sub(raw_name.len()));
+        CONSOLE.print(format!("<brightblue>{name}</brightblue>{padding}    {count:count_len$}    {p50:p50_len$}    {p95:p95_len$}    {rate}"));
+      }
+
+      Ok(())
+    }
+  }
+
+  mod implementation {
+    /// The `percent`th percentile of `sorted`, using the nearest rank.
+    fn percentile(sorted: &[Duration], percent: usize) -> Option<Duration> {
+      let rank = sorted.len().saturating_mul(percent).div_ceil(100);
+      sorted.get(rank.saturating_sub(1)).copied()
+    }
+  }
+}
@@ -147,17 +147,10 @@ struct_gen! {
     }
 
     fn format_data(self: &Self, data: Vec<(&'static str, impl Into<String>)>) -> () {
-      let mut data = data;
-      let mut max_key_len = 0;
-      data.sort_by(|a, b| {
-        max_key_len = max_key_len.max(a.0.len()).max(b.0.len());
-        a.0.cmp(b.0)
-      });
-
-      for (key, value) in data {
-        let spaces = " ".repeat(max_key_len.saturating_sub(key.len()).saturating_add(4));
-        CONSOLE.print(format!("<brightblue>{key}</brightblue>{spaces} {}", value.into()));
-      }
+      let mut data = data.into_iter().map(|(key, value)| (key.to_owned(), value.into())).collect::<Vec<(String, String)>>();
+      data.sort_by(|a, b| a.0.cmp(&b.0));
+
+      super::print_data(&data);
     }
   }
 }
@@ -0,0 +1,65 @@
+use std::{
+  fs::File,
+  io::{Read, Seek, SeekFrom, Write},
+  thread::sleep,
+  time::Duration,
+};
+
+use clap::Args;
+use std_v2::{command::Operation, console::CONSOLE, struct_gen};
+
+use crate::operations::run::job::Job;
+
+struct_gen! {
+  #[usage(Flags, Operand { name: "job".to_string() })]
+  pub struct Options use Args, std_v2::derive::Command {
+    #[arg(short = 'H', long), help]
+    let help: bool = false;
+
+    #[arg(short = 'F', long), flag("Keep printing new output until the job exits")]
+    let follow: bool = false;
+
+    #[arg()]
+    let job: Option<String> = None;
+  }
+
+  impl Operation {
+    const NAME: &'static str = "logs";
+
+    fn main(self: &Self) -> std::io::Result<()> {
+      (self.help).then(|| Self::usage(0));
+
+      let id = self.job.as_deref().unwrap_or_else(|| CONSOLE.exit("No job specified"));
+      let path = Job::log_path(id);
+
+      // `ctr ps` and `ctr stop` remove the record of jobs that are gone, but keep their log
+      let job = match Job::load(id) {
+        Some(job) => Some(job),
+        None if path.is_file() => None,
+        None => Some(Job::find(&self.job)),
+      };
+
+      let mut log = File::open(&path).unwrap_or_else(|err| CONSOLE.exit(format!("Failed to open {}: {err}", path.display())));
+      let mut stdout = std::io::stdout();
+
+      std::io::copy(&mut log, &mut stdout)?;
+
+      while self.follow && job.as_ref().is_some_and(Job::is_alive) {
+        sleep(Duration::from_millis(250));
+
+        // the log may have been truncated in the meantime
+        let position = log.stream_position()?;
+        if log.metadata()?.len() < position {
+          log.seek(SeekFrom::Start(0))?;
+        }
+
+        let mut buffer = vec![];
+        log.read_to_end(&mut buffer)?;
+        stdout.write_all(&buffer)?;
+        stdout.flush()?;
+      }
+
+      Ok(())
+    }
+  }
+}
@@ -3,9 +3,15 @@ use std_v2::console::CONSOLE;
 pub mod completions;
 pub mod env;
 pub mod help;
+pub mod history;
 pub mod info;
+pub mod logs;
 pub mod notify;
+pub mod preset;
+pub mod ps;
+pub mod restart;
 pub mod run;
+pub mod stop;
 pub mod upgrade;
 pub mod version;
 
@@ -32,3 +38,18 @@ pub fn check_conflicts(args: Vec<(&str, bool)>) {
     CONSOLE.exit(format!("Arguments {conflicts} cannot be used together"));
   }
 }
+
+/// Prints `data` as aligned key/value pairs, the layout used by `info`.
+pub fn print_data(data: &[(String, String)]) {
+  let max_key_len = data.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+
+  for (key, value) in data {
+    let spaces = " ".repeat(max_key_len.saturating_sub(key.len()).saturating_add(4));
+    CONSOLE.print(format!("<brightblue>{key}</brightblue>{spaces} {value}"));
+  }
+}
+
+/// Escapes user content, so that it is not parsed as styling.
+pub fn escape_markup(text: &str) -> String {
+  text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
@@ -30,32 +30,25 @@ struct_gen! {
     fn main(self: &Self) -> std::io::Result<()> {
       self.help.then(|| Self::usage(0));
 
-      let mut notification = notify_rust::Notification::new();
-      notification
-        .summary(&self.title.clone().map_or("ctr".to_string(), |e| {
-          if e.trim().is_empty() {
-            "ctr".to_string()
-          } else {
-            e
-          }
-        }))
-        .body(&self.args.join(" "));
-
-      if let Some(level) = self.level {
-        notification.urgency(match level {
-          0 => notify_rust::Urgency::Low,
-          2 => notify_rust::Urgency::Critical,
-          _ => notify_rust::Urgency::Normal,
-        });
-      }
-
-      if let Some(icon) = &self.icon {
-        if Path::new(icon).exists() {
-          notification.icon(icon);
+      let title = self.title.clone().map_or("ctr".to_string(), |e| {
+        if e.trim().is_empty() {
+          "ctr".to_string()
         } else {
-          CONSOLE.warn(format!("Icon file not found: {icon}"));
+          e
         }
-      }
+      });
+
+      let urgency = self.level.map(|level| match level {
+        0 => notify_rust::Urgency::Low,
+        2 => notify_rust::Urgency::Critical,
+        _ => notify_rust::Urgency::Normal,
+      });
+
+      let icon = self.icon.as_deref().filter(|icon| {
+        let exists = Path::new(icon).exists();
+        (!exists).then(|| CONSOLE.warn(format!("Icon file not found: {icon}")));
+        exists
+      });
 
       if let Some(delay) = self.delay {
         if delay > 0 {
@@ -63,7 +56,7 @@ struct_gen! {
         }
       }
 
-      if let Err(e) = notification.show() {
+      if let Err(e) = Self::show(&title, &self.args.join(" "), urgency, icon) {
         CONSOLE.exit(format!("{e}"));
       }
 
@@ -91,4 +84,22 @@ struct_gen! {
       Ok(())
     }
   }
+
+  mod implementation {
+    /// Raises a desktop notification, also used by `ctr run --notify`.
+    pub fn show(summary: &str, body: &str, urgency: Option<notify_rust::Urgency>, icon: Option<&str>) -> Result<(), notify_rust::error::Error> {
+      let mut notification = notify_rust::Notification::new();
+      notification.summary(summary).body(body);
+
+      if let Some(level) = urgency {
+        notification.urgency(level);
+      }
+
+      if let Some(path) = icon {
+        notification.icon(path);
+      }
+
+      notification.show().map(|_| ())
+    }
+  }
 }
@@ -0,0 +1,35 @@
+use clap::Args;
+use std_v2::{command::Operation, console::CONSOLE, struct_gen};
+
+use super::{existing_preset, new_preset};
+
+struct_gen! {
+  #[usage(Flags, Operand { name: "from".to_string() }, Operand { name: "to".to_string() })]
+  pub struct Options use Args, std_v2::derive::Command {
+    #[arg(short = 'H', long), help]
+    let help: bool = false;
+
+    #[arg()]
+    let from: Option<String> = None;
+
+    #[arg()]
+    let to: Option<String> = None;
+  }
+
+  impl Operation {
+    const NAME: &'static str = "cp";
+    const PARENT: Option<&'static str> = Some("preset");
+
+    fn main(self: &Self) -> std::io::Result<()> {
+      (self.help).then(|| Self::usage(0));
+
+      let from = existing_preset(&self.from);
+      let to = new_preset(&self.to);
+
+      std::fs::copy(&from, &to)?;
+      CONSOLE.print(format!("<brightmagenta>Copied {} to {}</brightmagenta>", from.display(), to.display()));
+
+      Ok(())
+    }
+  }
+}
@@ -0,0 +1,46 @@
+use std::process::Command;
+
+use clap::Args;
+use std_v2::{command::Operation, console::CONSOLE, struct_gen};
+
+use super::existing_preset;
+use crate::operations::run::ser::LaunchConfig;
+
+struct_gen! {
+  #[usage(Flags, Operand { name: "name".to_string() })]
+  pub struct Options use Args, std_v2::derive::Command {
+    #[arg(short = 'H', long), help]
+    let help: bool = false;
+
+    #[arg()]
+    let name: Option<String> = None;
+  }
+
+  impl Operation {
+    const NAME: &'static str = "edit";
+    const PARENT: Option<&'static str> = Some("preset");
+
+    fn main(self: &Self) -> std::io::Result<()> {
+      (self.help).then(|| Self::usage(0));
+
+      let path = existing_preset(&self.name);
+      let editor = std::env::var("VISUAL").or_else(|_| std::env::var("EDITOR")).unwrap_or("vi".to_owned());
+
+      // $EDITOR may contain arguments, e.g. `code --wait`
+      let mut editor_args = editor.split_whitespace();
+      let binary = editor_args.next().unwrap_or("vi");
+
+      match Command::new(binary).args(editor_args).arg(&path).status() {
+        Ok(status) if status.success() => {
+          if let Err(err) = LaunchConfig::from_file(&path) {
+            CONSOLE.warn(format!("{}: {}", path.display(), err.message()));
+          }
+        },
+        Ok(status) => CONSOLE.exit(format!("<brightmagenta>{editor}</brightmagenta> exited with {status}")),
+        Err(err) => CONSOLE.exit(format!("Failed to run `{editor}`: {err}")),
+      }
+
+      Ok(())
+    }
+  }
+}
@@ -0,0 +1,88 @@
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use std_v2::{command::Operation, console::CONSOLE, struct_gen};
+
+use crate::operations::run::{local_presets_dirs, ser::LaunchConfig, PresetScope, PRESETS_DIR};
+
+struct_gen! {
+  pub struct Options use Args, std_v2::derive::Command {
+    #[arg(short = 'H', long), help]
+    let help: bool = false;
+  }
+
+  impl Operation {
+    const NAME: &'static str = "list";
+    const PARENT: Option<&'static str> = Some("preset");
+
+    fn main(self: &Self) -> std::io::Result<()> {
+      (self.help).then(|| Self::usage(0));
+      Self::print_presets(PresetScope::Any);
+
+      Ok(())
+    }
+  }
+
+  mod implementation {
+    /// Prints the presets in `scope` with their commands. Project-local presets are marked as such.
+    pub fn print_presets(scope: PresetScope) {
+      let presets = Self::presets(scope);
+      if presets.is_empty() {
+        match scope {
+          PresetScope::Local => CONSOLE.print("No presets found in <bold>.ctr/presets</bold> of the current project"),
+          _ => CONSOLE.print(format!("No presets found in <bold>{}</bold>", PRESETS_DIR.display())),
+        }
+        return;
+      }
+
+      let max_name_len = presets.iter().map(|(name, _, _)| name.len()).max().unwrap_or(0);
+      for (name, path, local) in presets {
+        let spaces = " ".repeat(max_name_len.saturating_sub(name.len()).saturating_add(4));
+        let summary = match LaunchConfig::from_file(&path) {
+          Ok(config) => {
+            let command = config.general.command.map(|command| command.to_string()).unwrap_or_default();
+
+            // the command is user content and must not be parsed as styling
+            crate::operations::escape_markup(&command)
+          },
+          Err(_) => "<red>invalid</red>".to_owned(),
+        };
+
+        let marker = if local { "<yellow>local</yellow>  " } else { "       " };
+        CONSOLE.print(format!("<brightblue>{name}</brightblue>{spaces}{marker}<brightblack>{summary}</brightblack>"));
+      }
+    }
+
+    /// The presets in `scope` as `(name, path, local)`, sorted by name.
+    /// A preset shadows presets with the same name further up, and the global one.
+    pub fn presets(scope: PresetScope) -> Vec<(String, PathBuf, bool)> {
+      let mut presets: Vec<(String, PathBuf, bool)> = vec![];
+
+      let local_dirs = if scope == PresetScope::Global { vec![] } else { local_presets_dirs() };
+      let global_dirs = if scope == PresetScope::Local { vec![] } else { vec![PRESETS_DIR.to_path_buf()] };
+
+      for (dir, local) in local_dirs.into_iter().map(|dir| (dir, true)).chain(global_dirs.into_iter().map(|dir| (dir, false))) {
+        for (name, path) in Self::presets_in(&dir) {
+          if !presets.iter().any(|(existing, _, _)| *existing == name) {
+            presets.push((name, path, local));
+          }
+        }
+      }
+
+      presets.sort_by(|a, b| a.0.cmp(&b.0));
+      presets
+    }
+
+    fn presets_in(dir: &Path) -> Vec<(String, PathBuf)> {
+      match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+          .filter_map(|entry| entry.ok())
+          .map(|entry| entry.path())
+          .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+          .filter_map(|path| path.file_stem().map(|stem| (stem.to_string_lossy().to_string(), path.clone())))
+          .collect::<Vec<(String, PathBuf)>>(),
+        Err(_) => vec![],
+      }
+    }
+  }
+}
@@ -0,0 +1,115 @@
+use std::path::PathBuf;
+
+use clap::{Args, Subcommand};
+use std_v2::{command::Operation, console::CONSOLE, derive::Command, struct_gen};
+
+use super::run::{global_preset_path, preset_path, PRESETS_DIR};
+use crate::execute_command;
+
+pub mod cp;
+pub mod edit;
+pub mod list;
+pub mod new;
+pub mod rm;
+pub mod show;
+pub mod validate;
+
+use cp::Options as CpCommand;
+use edit::Options as EditCommand;
+use list::Options as ListCommand;
+use new::Options as NewCommand;
+use rm::Options as RmCommand;
+use show::Options as ShowCommand;
+use validate::Options as ValidateCommand;
+
+#[derive(Debug, Subcommand, Command)]
+#[non_exhaustive]
+pub enum Commands {
+  #[operation("List all presets")]
+  List(ListCommand),
+  #[operation("Show a preset after defaults are applied")]
+  Show(ShowCommand),
+  #[operation("Create a new preset")]
+  New(NewCommand),
+  #[operation("Open a preset in $EDITOR")]
+  Edit(EditCommand),
+  #[operation("Remove a preset")]
+  Rm(RmCommand),
+  #[operation("Copy a preset")]
+  Cp(CpCommand),
+  #[operation("Check presets for errors")]
+  Validate(ValidateCommand),
+}
+
+impl Operation for Commands {
+  const NAME: &'static str = "preset";
+
+  fn main(&self) -> std::io::Result<()> {
+    Ok(())
+  }
+}
+
+/// Returns the path of an existing preset, or exits if there is none.
+pub fn existing_preset(preset: &Option<String>) -> PathBuf {
+  let name = preset.as_deref().unwrap_or_else(|| CONSOLE.exit("No preset specified"));
+  let path = preset_path(name);
+
+  if !path.exists() {
+    CONSOLE.exit(format!("The preset <brightmagenta>{name}</brightmagenta> does not exist"));
+  }
+
+  path
+}
+
+/// Returns the path for a preset that is about to be created, or exits if it already exists.
+pub fn new_preset(preset: &Option<String>) -> PathBuf {
+  let name = preset.as_deref().unwrap_or_else(|| CONSOLE.exit("No preset specified"));
+
+  if name.is_empty() || name.contains('/') {
+    CONSOLE.exit(format!("<brightmagenta>{name}</brightmagenta> is not a valid preset name"));
+  }
+
+  // new presets are always global, project-local ones are created by hand in .ctr/presets
+  let path = global_preset_path(name);
+  if path.exists() {
+    CONSOLE.exit(format!("The preset <brightmagenta>{name}</brightmagenta> already exists"));
+  }
+
+  if let Err(err) = std::fs::create_dir_all(&*PRESETS_DIR) {
+    CONSOLE.exit(format!("Failed to create {}: {err}", PRESETS_DIR.display()));
+  }
+
+  path
+}
+
+struct_gen! {
+  #[usage(Flags, Operand { name: "operation".to_string() })]
+  pub struct Options use Args, Command {
+    #[command(subcommand)]
+    let command: Option<Commands> = None;
+
+    #[arg(short = 'H', long), help]
+    let help: bool = false;
+  }
+
+  impl Operation {
+    const NAME: &'static str = "preset";
+
+    fn main(self: &Self) -> std::io::Result<()> {
+      (self.help).then(|| Self::usage(0));
+
+      match self.command {
+        Some(Commands::List(ref options)) => execute_command(options),
+        Some(Commands::Show(ref options)) => execute_command(options),
+        Some(Commands::New(ref options)) => execute_command(options),
+        Some(Commands::Edit(ref options)) => execute_command(options),
+        Some(Commands::Rm(ref options)) => execute_command(options),
+        Some(Commands::Cp(ref options)) => execute_command(options),
+        Some(Commands::Validate(ref options)) => execute_command(options),
+        None => Self::usage(0),
+      }
+
+      Ok(())
+    }
+  }
+}
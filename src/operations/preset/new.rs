@@ -0,0 +1,139 @@
+use clap::Args;
+use std_v2::{command::Operation, console::CONSOLE, struct_gen};
+
+use super::new_preset;
+
+const TEMPLATE: &str = r#"# String values support `~`, ${VAR}, ${VAR:-default} and ${VAR:?error}, as well as ${CTR_PRESET} and ${CTR_CONFIG_DIR}.
+# Variables from [environment] and env_files can be used as well. `$VAR` is expanded too, and `$$` is a literal `$`,
+# except in a `command` string and `ready.command`, which run in a shell: there, `$VAR`, `$$` and any `${...}` ctr does not know are left to the shell.
+
+# extends = ["base"]
+# unset_environment = []
+# env_files = [".env", { path = "~/.secrets/api.env", optional = true }] # loaded before [environment]
+# depends_on = ["db"] # started first, and stopped when this preset exits, cannot be daemonized
+
+[general]
+command = ""
+# shell = "/bin/bash"
+# working_dir = "~"
+# preserve_env = true
+# deamonize = false
+# restart = "no" # "on-failure" or "always"
+# max_restarts = 5
+# restart_delay = "1s"
+# restart_max_delay = "30s"
+# timeout = "5m"
+# timeout_grace = "10s"
+# stop_signal = "SIGTERM"
+# stop_grace = "10s"
+# tty = false # run the command under a pseudo-terminal, so it keeps colors when its output is logged
+# umask = "022"
+
+# [run_as]
+# elevate = "sudo" # or "doas", "run0", "pkexec", "su", or "auto" for the first one installed
+# sudo = false # uses run_as.elevate from config.toml in the config directory, "auto" by default
+# user = "root"
+# group = "root"
+# login_shell = false # run through the login shell of the user, so that its profile is loaded
+
+[environment]
+
+# [limits]
+# nofile = 1024
+# nproc = 512
+# as = "4G"
+# cpu = 3600
+# core = 0
+# hard = false # also set the hard limits, raising them needs root
+
+# [scheduling]
+# nice = 10
+# ioprio_class = "best-effort" # "realtime" or "idle"
+# ioprio_level = 4
+# cpu_affinity = "0-3"
+
+# [log]
+# path = "/tmp/preset.log"
+# append = true
+# max_size = "10M"
+# keep = 5
+# timestamps = false
+# prefix = false # prefix lines with [stdout] or [stderr]
+
+# how presets that depend on this one know it is ready, all configured checks must pass
+# [ready]
+# tcp = 5432 # or "host:port"
+# http = "http://localhost:8080/health"
+# file = "/tmp/ready"
+# log = "ready to accept connections" # regex matched against the output
+# command = "pg_isready"
+# timeout = "30s"
+# interval = "500ms"
+
+# runs the command again when files change, like `--watch`. `.gitignore` files are respected, and the command cannot read from the terminal
+# [watch]
+# paths = ["src", "Cargo.toml"] # or globs like "src/**/*.rs"
+# ignore = ["*.log"]
+# debounce = "200ms"
+# on_change = "restart" # or "queue" to let the command finish first
+
+# isolates the command with unprivileged user namespaces
+# [sandbox]
+# network = false # only loopback
+# private_tmp = true
+# private_pid = true
+# read_only = ["/usr", "~"]
+# writable = ["./target"]
+# hide = ["~/.ssh"]
+
+# shows a desktop notification when the command finishes, like `--notify`
+# [notify]
+# on = "always" # "failure" or "success"
+# min_duration = "30s"
+
+# runs the member presets in parallel instead of general.command
+# [group]
+# members = ["api", "worker"]
+# fail_fast = false
+
+# [hooks]
+# before = []
+# after = [{ command = "", policy = "warn" }] # runs after on_success or on_failure, even if one of them aborted
+# on_success = []
+# on_failure = []
+
+# `{1}`, `{2}` and `{@}` in `general.command` are replaced by the arguments after the preset name,
+# `{name}` by the parameter set with `--param name=value` or `--name value`,
+# and values are quoted for the shell. With placeholders but without `{@}`, extra arguments are an error
+# [params.name]
+# default = ""
+# required = false
+# choices = []
+# description = ""
+"#;
+
+struct_gen! {
+  #[usage(Flags, Operand { name: "name".to_string() })]
+  pub struct Options use Args, std_v2::derive::Command {
+    #[arg(short = 'H', long), help]
+    let help: bool = false;
+
+    #[arg()]
+    let name: Option<String> = None;
+  }
+
+  impl Operation {
+    const NAME: &'static str = "new";
+    const PARENT: Option<&'static str> = Some("preset");
+
+    fn main(self: &Self) -> std::io::Result<()> {
+      (self.help).then(|| Self::usage(0));
+
+      let path = new_preset(&self.name);
+      std::fs::write(&path, TEMPLATE)?;
+      CONSOLE.print(format!("<brightmagenta>Created {}</brightmagenta>", path.display()));
+
+      Ok(())
+    }
+  }
+}
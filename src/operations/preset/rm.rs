@@ -0,0 +1,30 @@
+use clap::Args;
+use std_v2::{command::Operation, console::CONSOLE, struct_gen};
+
+use super::existing_preset;
+
+struct_gen! {
+  #[usage(Flags, Operand { name: "name".to_string() })]
+  pub struct Options use Args, std_v2::derive::Command {
+    #[arg(short = 'H', long), help]
+    let help: bool = false;
+
+    #[arg()]
+    let name: Option<String> = None;
+  }
+
+  impl Operation {
+    const NAME: &'static str = "rm";
+    const PARENT: Option<&'static str> = Some("preset");
+
+    fn main(self: &Self) -> std::io::Result<()> {
+      (self.help).then(|| Self::usage(0));
+
+      let path = existing_preset(&self.name);
+      std::fs::remove_file(&path)?;
+      CONSOLE.print(format!("<brightmagenta>Removed {}</brightmagenta>", path.display()));
+
+      Ok(())
+    }
+  }
+}
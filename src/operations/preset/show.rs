@@ -0,0 +1,31 @@
+use clap::Args;
+use std_v2::{command::Operation, struct_gen};
+
+use super::existing_preset;
+use crate::operations::run::ser::LaunchConfig;
+
+struct_gen! {
+  #[usage(Flags, Operand { name: "name".to_string() })]
+  pub struct Options use Args, std_v2::derive::Command {
+    #[arg(short = 'H', long), help]
+    let help: bool = false;
+
+    #[arg()]
+    let name: Option<String> = None;
+  }
+
+  impl Operation {
+    const NAME: &'static str = "show";
+    const PARENT: Option<&'static str> = Some("preset");
+
+    fn main(self: &Self) -> std::io::Result<()> {
+      (self.help).then(|| Self::usage(0));
+
+      let config = LaunchConfig::resolve(&existing_preset(&self.name));
+      // printed as-is, so the output can be piped into a new preset
+      println!("{}", std_v2::toml::stringify(&config).trim_end());
+
+      Ok(())
+    }
+  }
+}
@@ -0,0 +1,71 @@
+use std::path::Path;
+
+use clap::Args;
+use std_v2::{command::Operation, console::CONSOLE, struct_gen};
+
+use super::{existing_preset, list::Options as ListCommand};
+use crate::operations::run::{ser::LaunchConfig, PresetScope};
+
+struct_gen! {
+  #[usage(Flags, Operand { name: "name".to_string() })]
+  pub struct Options use Args, std_v2::derive::Command {
+    #[arg(short = 'H', long), help]
+    let help: bool = false;
+
+    #[arg()]
+    let name: Option<String> = None;
+  }
+
+  impl Operation {
+    const NAME: &'static str = "validate";
+    const PARENT: Option<&'static str> = Some("preset");
+
+    fn main(self: &Self) -> std::io::Result<()> {
+      (self.help).then(|| Self::usage(0));
+
+      let paths = if self.name.is_some() {
+        vec![existing_preset(&self.name)]
+      } else {
+        ListCommand::presets(PresetScope::Any).into_iter().map(|(_, path, _)| path).collect()
+      };
+
+      let failed = paths.iter().filter(|path| !Self::validate_file(path)).count();
+      if failed > 0 {
+        CONSOLE.exit(format!("{failed} of {} presets are invalid", paths.len()));
+      }
+
+      Ok(())
+    }
+  }
+
+  mod implementation {
+    fn validate_file(path: &Path) -> bool {
+      let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) => {
+          CONSOLE.error(format!("{}: {err}", path.display()));
+          return false;
+        }
+      };
+
+      match std_v2::toml::parse::<LaunchConfig>(contents.as_str()) {
+        Ok(_) => {
+          CONSOLE.print(format!("<green>ok</green>      {}", path.display()));
+          true
+        },
+        Err(err) => {
+          // toml only reports byte offsets, so the position is recovered from the contents
+          let (line, column) = err.span().map_or((1, 1), |span| {
+            let before = contents.bytes().take(span.start);
+            let line = before.clone().filter(|byte| *byte == b'\n').count().saturating_add(1);
+            let column = before.rev().take_while(|byte| *byte != b'\n').count().saturating_add(1);
+            (line, column)
+          });
+
+          CONSOLE.print(format!("<red>invalid</red> {}:{line}:{column}: {}", path.display(), err.message().trim_end()));
+          false
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+use clap::Args;
+use std_v2::{command::Operation, console::CONSOLE, struct_gen};
+
+use crate::operations::run::job::{Job, JOBS_DIR};
+
+struct_gen! {
+  pub struct Options use Args, std_v2::derive::Command {
+    #[arg(short = 'H', long), help]
+    let help: bool = false;
+  }
+
+  impl Operation {
+    const NAME: &'static str = "ps";
+
+    fn main(self: &Self) -> std::io::Result<()> {
+      (self.help).then(|| Self::usage(0));
+
+      let (running, stale): (Vec<Job>, Vec<Job>) = Job::load_all().into_iter().partition(|job| job.is_alive());
+
+      // jobs that exited on their own, or whose pid now belongs to another process
+      for job in &stale {
+        CONSOLE.info(format!("<brightmagenta>{}</brightmagenta> is no longer running, removing its record", job.id));
+        job.remove();
+      }
+
+      if running.is_empty() {
+        CONSOLE.print(format!("No running jobs in <bold>{}</bold>", JOBS_DIR.display()));
+        return Ok(());
+      }
+
+      let rows = running.iter()
+        .map(|job| (job.id.clone(), job.pid.to_string(), Self::format_uptime(job.uptime()), job.command.clone()))
+        .collect::<Vec<(String, String, String, String)>>();
+      let id_len = rows.iter().map(|row| row.0.len()).chain([2]).max().unwrap_or(0);
+      let pid_len = rows.iter().map(|row| row.1.len()).chain([3]).max().unwrap_or(0);
+      let uptime_len = rows.iter().map(|row| row.2.len()).chain([6]).max().unwrap_or(0);
+
+      CONSOLE.print(format!("<bold>{:id_len$}    {:pid_len$}    {:uptime_len$}    COMMAND</bold>", "ID", "PID", "UPTIME"));
+      for (id, pid, uptime, command) in rows {
+        // the command is user content and must not be parsed as styling
+        let escaped = crate::operations::escape_markup(&command);
+        CONSOLE.print(format!("<brightblue>{id:id_len$}</brightblue>    {pid:pid_len$}    {uptime:uptime_len$}    <brightblack>{escaped}</brightblack>"));
+      }
+
+      Ok(())
+    }
+  }
+
+  mod implementation {
+    /// Formats `uptime` using its two largest units, like `3d 4h` or `5m 12s`.
+    pub fn format_uptime(uptime: Duration) -> String {
+      let seconds = uptime.as_secs();
+      let units = [
+        (seconds / 86_400, "d"),
+        (seconds / 3_600 % 24, "h"),
+        (seconds / 60 % 60, "m"),
+        (seconds % 60, "s"),
+      ];
+
+      let parts = units.iter()
+        .skip_while(|(value, _)| *value == 0)
+        .take(2)
+        .map(|(value, unit)| format!("{value}{unit}"))
+        .collect::<Vec<String>>();
+
+      if parts.is_empty() {
+        "0s".to_owned()
+      } else {
+        parts.join(" ")
+      }
+    }
+  }
+}
@@ -0,0 +1,46 @@
+use std::process::Command;
+
+use clap::Args;
+use std_v2::{command::Operation, console::CONSOLE, struct_gen};
+
+use crate::operations::run::{
+  duration::HumanDuration,
+  job::{Job, RESTART_ID_VAR},
+  signals::exit_code,
+};
+
+struct_gen! {
+  #[usage(Flags, Operand { name: "job".to_string() })]
+  pub struct Options use Args, std_v2::derive::Command {
+    #[arg(short = 'H', long), help]
+    let help: bool = false;
+
+    #[arg(short, long), flag("Time to wait after the stop signal before sending SIGKILL", example = "10s")]
+    let timeout: Option<HumanDuration> = None;
+
+    #[arg()]
+    let job: Option<String> = None;
+  }
+
+  impl Operation {
+    const NAME: &'static str = "restart";
+
+    fn main(self: &Self) -> std::io::Result<()> {
+      (self.help).then(|| Self::usage(0));
+
+      let job = Job::find(&self.job);
+      job.stop(self.timeout.map(|timeout| timeout.0));
+
+      // start it again exactly as it was invoked, which daemonizes it under a new record
+      let executable = std::env::current_exe()?;
+      let status = Command::new(executable)
+        .args(job.argv.iter().skip(1))
+        .current_dir(&job.invoked_from)
+        .env(RESTART_ID_VAR, &job.id)
+        .status()
+        .unwrap_or_else(|err| CONSOLE.exit(format!("Failed to restart <brightmagenta>{}</brightmagenta>: {err}", job.id)));
+
+      std::process::exit(exit_code(status));
+    }
+  }
+}
@@ -0,0 +1,284 @@
+use std::{
+  io::IsTerminal,
+  net::{TcpStream, ToSocketAddrs},
+  os::unix::process::CommandExt,
+  path::{Path, PathBuf},
+  process::{Child, Command, Stdio},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  thread::{sleep, JoinHandle},
+  time::{Duration, Instant},
+};
+
+use regex::bytes::Regex;
+use std_v2::{console::CONSOLE, struct_gen};
+
+use super::{
+  group::{self, forward, interrupted, Group},
+  job::Job,
+  locate_preset,
+  ser::{LaunchConfig, LaunchConfigReady},
+  signals::signal_group,
+  PresetScope, POLL_INTERVAL,
+};
+
+/// Set for presets started as dependencies, which must not start the dependencies of the whole stack again.
+pub const SKIP_DEPENDENCIES_VAR: &str = "CTR_SKIP_DEPENDENCIES";
+const DEFAULT_READY_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_READY_INTERVAL: Duration = Duration::from_millis(500);
+const GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+struct_gen! {
+  /// A preset started by `depends_on`, as its own `ctr run <name>`.
+  pub struct Dependency {
+    pub let name: String = String::new();
+    pub let ready: LaunchConfigReady = LaunchConfigReady::default();
+    /// `None` if the preset was already running as a daemonized job.
+    pub let child: Option<Child> = None;
+    pub let log_matched: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    pub let tees: Vec<JoinHandle<()>> = Vec::new();
+  }
+
+  mod implementation {
+    /// Whether all configured probes pass.
+    fn is_ready(self: &Self) -> bool {
+      let ready = &self.ready;
+
+      let tcp = ready.tcp.as_ref().is_none_or(|target| {
+        target.to_string().to_socket_addrs().ok()
+          .and_then(|mut addresses| addresses.next())
+          .is_some_and(|address| TcpStream::connect_timeout(&address, Duration::from_secs(1)).is_ok())
+      });
+
+      let http = ready.http.as_ref().is_none_or(|url| {
+        reqwest::blocking::Client::builder().timeout(Duration::from_secs(2)).build().ok()
+          .and_then(|client| client.get(url).send().ok())
+          .is_some_and(|response| response.status().is_success())
+      });
+
+      let file = ready.file.as_ref().is_none_or(|file| Path::new(file).exists());
+      // the output of a preset that was already running as a job cannot be read, it can only be checked by the other probes
+      let log = ready.log.is_none() || self.child.is_none() || self.log_matched.load(Ordering::SeqCst);
+
+      let command = ready.command.as_ref().is_none_or(|command| {
+        Command::new("sh").arg("-c").arg(command)
+          .stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null())
+          .status()
+          .is_ok_and(|status| status.success())
+      });
+
+      tcp && http && file && log && command
+    }
+  }
+}
+
+struct_gen! {
+  /// The dependencies of a preset, in the order they were started.
+  pub struct Dependencies {
+    pub let started: Vec<Dependency> = Vec::new();
+  }
+
+  mod constructors {
+    /// Orders the transitive dependencies of `preset` so that every preset comes after the ones it depends on.
+    pub fn resolve(preset: &str, config: &LaunchConfig, scope: PresetScope) -> Vec<(String, LaunchConfig)> {
+      let mut order = vec![];
+      let mut chain = vec![preset.to_owned()];
+
+      for dependency in config.depends_on.iter().flatten() {
+        Self::visit(dependency, scope, &mut chain, &mut order);
+      }
+
+      order
+    }
+
+    fn visit(name: &str, scope: PresetScope, chain: &mut Vec<String>, order: &mut Vec<(String, LaunchConfig)>) {
+      if chain.iter().any(|visited| visited == name) {
+        chain.push(name.to_owned());
+        CONSOLE.exit(format!("Presets cannot depend on each other in a cycle: {}", chain.join(" -> ")));
+      }
+
+      if order.iter().any(|(visited, _)| visited == name) {
+        return;
+      }
+
+      let path: PathBuf = locate_preset(name, scope).unwrap_or_else(|| {
+        CONSOLE.exit(format!("The preset <brightmagenta>{name}</brightmagenta> required by <brightmagenta>{}</brightmagenta> does not exist", chain.last().map_or("", |last| last.as_str())))
+      });
+
+      let config = LaunchConfig::resolve(&path);
+
+      chain.push(name.to_owned());
+      for dependency in config.depends_on.iter().flatten() {
+        Self::visit(dependency, scope, chain, order);
+      }
+      chain.pop();
+
+      order.push((name.to_owned(), config));
+    }
+
+    /// Starts the dependencies in order, waiting for each to become ready before starting the next.
+    /// Exits, after stopping the ones that were already started, if one fails or is not ready in time.
+    pub fn start(order: Vec<(String, LaunchConfig)>, flags: &[String]) -> Self {
+      let mut dependencies = Self::default();
+      if order.is_empty() {
+        return dependencies;
+      }
+
+      group::catch_interrupts();
+
+      let executable = std::env::current_exe().unwrap_or_else(|err| CONSOLE.exit(format!("Failed to start the dependencies: {err}")));
+      let parent = libc::pid_t::try_from(std::process::id()).unwrap_or(0_i32);
+      let width = order.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+
+      for (index, (name, config)) in order.into_iter().enumerate() {
+        let mut dependency = Dependency { name: name.clone(), ready: config.ready.unwrap_or_default(), ..Dependency::default() };
+
+        if Job::load(&name).is_some_and(|job| job.is_alive()) {
+          CONSOLE.info(format!("<brightmagenta>{name}</brightmagenta> is already running as a job"));
+          if dependency.ready.log.is_some() {
+            CONSOLE.warn(format!("The output of <brightmagenta>{name}</brightmagenta> cannot be read, <brightblue>ready.log</brightblue> is skipped"));
+          }
+        } else {
+          let mut command = Command::new(&executable);
+          command.arg("run").args(flags).arg(&name)
+            .env(SKIP_DEPENDENCIES_VAR, "1")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0);
+
+          // stops the dependency if ctr exits without stopping it, e.g. because the preset failed to start
+          // SAFETY: the closure only performs async-signal-safe syscalls
+          unsafe {
+            command.pre_exec(move || {
+              if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM) == -1_i32 {
+                return Err(std::io::Error::last_os_error());
+              }
+
+              // ctr may have exited before the signal was set up
+              if libc::getppid() != parent {
+                libc::raise(libc::SIGTERM);
+              }
+
+              Ok(())
+            });
+          }
+
+          let mut child = command.spawn().unwrap_or_else(|err| {
+            dependencies.stop();
+            CONSOLE.exit(format!("Failed to start <brightmagenta>{name}</brightmagenta>: {err}"))
+          });
+
+          let pattern = match dependency.ready.log.as_deref().map(Regex::new) {
+            Some(Ok(pattern)) => Some(pattern),
+            Some(Err(err)) => {
+              dependencies.stop();
+              CONSOLE.exit(format!("<brightblue>ready.log</brightblue> of <brightmagenta>{name}</brightmagenta> is not a valid regex: {err}"))
+            },
+            None => None,
+          };
+
+          if let Some(stdout) = child.stdout.take() {
+            let on_line = log_matcher(Arc::clone(&dependency.log_matched), pattern.clone());
+            dependency.tees.push(forward(stdout, std::io::stdout(), Group::prefix(&name, index, width, std::io::stdout().is_terminal()), on_line));
+          }
+
+          if let Some(stderr) = child.stderr.take() {
+            let on_line = log_matcher(Arc::clone(&dependency.log_matched), pattern);
+            dependency.tees.push(forward(stderr, std::io::stderr(), Group::prefix(&name, index, width, std::io::stderr().is_terminal()), on_line));
+          }
+
+          dependency.child = Some(child);
+        }
+
+        dependencies.started.push(dependency);
+        dependencies.wait_ready();
+      }
+
+      dependencies
+    }
+  }
+
+  mod implementation {
+    /// Waits until the most recently started dependency is ready.
+    fn wait_ready(self: &mut Self) {
+      let Some(dependency) = self.started.last_mut() else {
+        return;
+      };
+
+      let name = dependency.name.clone();
+      let timeout = dependency.ready.timeout.map_or(DEFAULT_READY_TIMEOUT, |timeout| timeout.0);
+      let interval = dependency.ready.interval.map_or(DEFAULT_READY_INTERVAL, |interval| interval.0);
+      let deadline = Instant::now().checked_add(timeout).unwrap_or_else(Instant::now);
+
+      let failure = loop {
+        if interrupted() {
+          break None;
+        }
+
+        if let Some(Ok(Some(status))) = dependency.child.as_mut().map(Child::try_wait) {
+          break Some(format!("<brightmagenta>{name}</brightmagenta> exited before it was ready: {status}"));
+        }
+
+        if dependency.is_ready() {
+          CONSOLE.info(format!("<brightmagenta>{name}</brightmagenta> is ready"));
+          return;
+        }
+
+        if Instant::now() >= deadline {
+          break Some(format!("<brightmagenta>{name}</brightmagenta> was not ready within {}", super::duration::HumanDuration(timeout)));
+        }
+
+        let next_check = Instant::now().checked_add(interval).unwrap_or_else(Instant::now);
+        while Instant::now() < next_check && !interrupted() {
+          sleep(POLL_INTERVAL);
+        }
+      };
+
+      self.stop();
+      match failure {
+        Some(message) => CONSOLE.exit(message),
+        None => std::process::exit(130_i32),
+      }
+    }
+
+    /// Stops the dependencies that were started by ctr, the most recently started first.
+    pub fn stop(self: &mut Self) {
+      for dependency in self.started.iter_mut().rev() {
+        let Some(ref mut child) = dependency.child else {
+          continue;
+        };
+
+        if let Ok(None) = child.try_wait() {
+          signal_group(child, libc::SIGTERM);
+
+          let kill_at = Instant::now().checked_add(GRACE_PERIOD).unwrap_or_else(Instant::now);
+          while Instant::now() < kill_at && matches!(child.try_wait(), Ok(None)) {
+            sleep(POLL_INTERVAL);
+          }
+
+          if let Ok(None) = child.try_wait() {
+            CONSOLE.warn(format!("<brightmagenta>{}</brightmagenta> is still running, sending <yellow>SIGKILL</yellow>", dependency.name));
+            signal_group(child, libc::SIGKILL);
+            let _ = child.wait();
+          }
+        }
+
+        for tee in dependency.tees.drain(..) {
+          let _ = tee.join();
+        }
+      }
+    }
+  }
+}
+
+/// Sets `matched` once a line of output matches `pattern`, for the `ready.log` probe.
+fn log_matcher(matched: Arc<AtomicBool>, pattern: Option<Regex>) -> impl Fn(&[u8]) + Send + 'static {
+  move |line: &[u8]| {
+    if pattern.as_ref().is_some_and(|regex| regex.is_match(line)) {
+      matched.store(true, Ordering::SeqCst);
+    }
+  }
+}
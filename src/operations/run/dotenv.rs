@@ -0,0 +1,242 @@
+use std::{iter::Peekable, str::Chars};
+
+/// Parses a dotenv file into its variables, in the order they are defined.
+///
+/// Supports `export` prefixes, `#` comments, single quoted (literal) and double quoted values,
+/// both of which may span multiple lines, and `$VAR`, `${VAR}` and `${VAR:-default}` expansion in unquoted and double quoted values.
+/// Variables are expanded against the ones defined before them, then `lookup`.
+pub fn parse(contents: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<Vec<(String, String)>, String> {
+  let mut parser = Parser { chars: contents.chars().peekable(), line: 1, vars: vec![] };
+
+  loop {
+    parser.skip_while(char::is_whitespace);
+    if parser.chars.peek().is_none() {
+      break;
+    }
+
+    let line = parser.line;
+    parser.entry(lookup).map_err(|err| format!("line {line}: {err}"))?;
+  }
+
+  Ok(parser.vars)
+}
+
+struct Parser<'a> {
+  chars: Peekable<Chars<'a>>,
+  line: usize,
+  vars: Vec<(String, String)>,
+}
+
+impl Parser<'_> {
+  fn next(&mut self) -> Option<char> {
+    let next = self.chars.next();
+    if next == Some('\n') {
+      self.line = self.line.saturating_add(1);
+    }
+
+    next
+  }
+
+  fn skip_while(&mut self, predicate: impl Fn(char) -> bool) {
+    while self.chars.peek().is_some_and(|c| predicate(*c)) {
+      self.next();
+    }
+  }
+
+  fn skip_line(&mut self) {
+    self.skip_while(|c| c != '\n');
+    self.next();
+  }
+
+  /// Reads a name, which may contain dots when used as a key but not when it is expanded.
+  fn word(&mut self, dots: bool) -> String {
+    let mut word = String::new();
+    while let Some(c) = self.chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_' || (dots && *c == '.')) {
+      word.push(c);
+    }
+
+    word
+  }
+
+  /// Parses one `KEY=value` line, or skips a comment.
+  fn entry(&mut self, lookup: &dyn Fn(&str) -> Option<String>) -> Result<(), String> {
+    if self.chars.next_if_eq(&'#').is_some() {
+      self.skip_line();
+      return Ok(());
+    }
+
+    let mut key = self.word(true);
+    if key == "export" && self.chars.peek().is_some_and(|c| *c == ' ' || *c == '\t') {
+      self.skip_while(|c| c == ' ' || c == '\t');
+      key = self.word(true);
+    }
+
+    if key.is_empty() || key.starts_with(|c: char| c.is_ascii_digit()) {
+      return Err("expected a variable name".to_owned());
+    }
+
+    self.skip_while(|c| c == ' ' || c == '\t');
+    if self.next() != Some('=') {
+      return Err(format!("expected `=` after `{key}`"));
+    }
+
+    self.skip_while(|c| c == ' ' || c == '\t');
+    let value = match self.chars.peek() {
+      Some('\'') => self.single_quoted()?,
+      Some('"') => self.double_quoted(lookup)?,
+      _ => self.unquoted(lookup)?,
+    };
+
+    self.skip_while(|c| c == ' ' || c == '\t');
+    match self.chars.peek() {
+      None | Some('\n' | '\r' | '#') => self.skip_line(),
+      Some(c) => return Err(format!("unexpected `{c}` after the value of `{key}`")),
+    }
+
+    self.vars.push((key, value));
+    Ok(())
+  }
+
+  fn single_quoted(&mut self) -> Result<String, String> {
+    self.next();
+
+    let mut value = String::new();
+    loop {
+      match self.next() {
+        Some('\'') => return Ok(value),
+        Some(c) => value.push(c),
+        None => return Err("unterminated `'`".to_owned()),
+      }
+    }
+  }
+
+  fn double_quoted(&mut self, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String, String> {
+    self.next();
+
+    let mut value = String::new();
+    loop {
+      match self.next() {
+        Some('"') => return Ok(value),
+        Some('\\') => match self.next() {
+          Some('n') => value.push('\n'),
+          Some('t') => value.push('\t'),
+          Some('r') => value.push('\r'),
+          Some(c @ ('"' | '\\' | '$')) => value.push(c),
+          Some(c) => {
+            value.push('\\');
+            value.push(c);
+          },
+          None => return Err("unterminated `\"`".to_owned()),
+        },
+        Some('$') => value.push_str(&self.expand(lookup)?),
+        Some(c) => value.push(c),
+        None => return Err("unterminated `\"`".to_owned()),
+      }
+    }
+  }
+
+  /// Reads up to the end of the line or an inline ` #` comment.
+  fn unquoted(&mut self, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut value = String::new();
+    while let Some(next) = self.chars.next_if(|c| *c != '\n') {
+      match next {
+        '#' if value.is_empty() || value.ends_with([' ', '\t']) => {
+          self.skip_while(|c| c != '\n');
+          break;
+        },
+        '$' => value.push_str(&self.expand(lookup)?),
+        other => value.push(other),
+      }
+    }
+
+    Ok(value.trim_end().to_owned())
+  }
+
+  /// Expands the variable after a `$`, which has already been consumed.
+  /// `${VAR:-default}` falls back to `default` if `VAR` is unset or empty, `${VAR-default}` only if it is unset.
+  fn expand(&mut self, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String, String> {
+    if self.chars.next_if_eq(&'{').is_none() {
+      let name = self.word(false);
+      return Ok(if name.is_empty() { "$".to_owned() } else { self.lookup(&name, lookup).unwrap_or_default() });
+    }
+
+    let name = self.word(false);
+    if name.is_empty() {
+      return Err("expected a variable name after `${`".to_owned());
+    }
+
+    let value = self.lookup(&name, lookup);
+    let empty_is_unset = self.chars.next_if_eq(&':').is_some();
+    match self.chars.next_if(|c| *c != '\n') {
+      Some('}') if !empty_is_unset => Ok(value.unwrap_or_default()),
+      Some('-') => {
+        let default = self.default_value(lookup)?;
+        Ok(value.filter(|set| !(empty_is_unset && set.is_empty())).unwrap_or(default))
+      },
+      Some(c) => Err(format!("unsupported `{c}` in `${{{name}`")),
+      None => Err(format!("unterminated `${{{name}`")),
+    }
+  }
+
+  /// Reads the default of `${VAR:-default}` up to the closing brace, without leaving the line.
+  fn default_value(&mut self, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut default = String::new();
+    loop {
+      match self.chars.next_if(|c| *c != '\n') {
+        Some('}') => return Ok(default),
+        Some('$') => default.push_str(&self.expand(lookup)?),
+        Some(c) => default.push(c),
+        None => return Err("unterminated `${`".to_owned()),
+      }
+    }
+  }
+
+  /// A variable defined earlier in the file, followed by `lookup`.
+  fn lookup(&self, name: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Option<String> {
+    self.vars.iter().rev().find(|(key, _)| key == name).map(|(_, value)| value.to_owned()).or_else(|| lookup(name))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::parse;
+
+  fn vars(contents: &str) -> Result<Vec<(String, String)>, String> {
+    parse(contents, &|name| (name == "OUTER").then(|| "outer".to_owned()))
+  }
+
+  fn pairs(expected: &[(&str, &str)]) -> Result<Vec<(String, String)>, String> {
+    Ok(expected.iter().map(|(key, value)| ((*key).to_owned(), (*value).to_owned())).collect())
+  }
+
+  #[test]
+  fn parses_quotes_comments_and_export() {
+    let contents = "# comment\nexport A=1 # inline\nB='lit $A'\nC=\"two\nlines \\\"$A\\\"\"\nD=a#b\n";
+    assert_eq!(vars(contents), pairs(&[("A", "1"), ("B", "lit $A"), ("C", "two\nlines \"1\""), ("D", "a#b")]));
+  }
+
+  #[test]
+  fn expands_earlier_variables_then_lookup() {
+    assert_eq!(vars("A=x\nB=$A-${A}-$OUTER-${MISSING}-$\n"), pairs(&[("A", "x"), ("B", "x-x-outer--$")]));
+  }
+
+  #[test]
+  fn expands_defaults() {
+    let contents = "EMPTY=\nA=${FOO:-bar}\nB=\"${EMPTY:-empty} ${EMPTY-unset}\"\nC=${OUTER:-x} ${FOO:-${OUTER}}\n";
+    assert_eq!(vars(contents), pairs(&[("EMPTY", ""), ("A", "bar"), ("B", "empty "), ("C", "outer outer")]));
+  }
+
+  #[test]
+  fn fails_on_invalid_expansions_without_joining_lines() {
+    assert_eq!(vars("A=1\nB=${FOO\nC=3\n"), Err("line 2: unterminated `${FOO`".to_owned()));
+    assert_eq!(vars("A=${FOO:-bar\nC=3\n"), Err("line 1: unterminated `${`".to_owned()));
+    assert_eq!(vars("A=${FOO%%x}\n"), Err("line 1: unsupported `%` in `${FOO`".to_owned()));
+    assert_eq!(vars("A=${}\n"), Err("line 1: expected a variable name after `${`".to_owned()));
+  }
+
+  #[test]
+  fn fails_on_unterminated_quotes() {
+    assert_eq!(vars("A=\"open\n"), Err("line 1: unterminated `\"`".to_owned()));
+    assert_eq!(vars("A=1\nB='open"), Err("line 2: unterminated `'`".to_owned()));
+  }
+}
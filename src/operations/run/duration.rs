@@ -0,0 +1,97 @@
+use std::{
+  fmt::{Display, Formatter},
+  str::FromStr,
+  time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// A duration written as `500ms`, `90s`, `5m`, `2h`, `1d` or combined like `1h30m`.
+/// Plain numbers are seconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "RawDuration", into = "String")]
+pub struct HumanDuration(pub Duration);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawDuration {
+  Seconds(u64),
+  Text(String),
+}
+
+impl TryFrom<RawDuration> for HumanDuration {
+  type Error = String;
+
+  fn try_from(raw: RawDuration) -> Result<Self, Self::Error> {
+    match raw {
+      RawDuration::Seconds(seconds) => Ok(Self(Duration::from_secs(seconds))),
+      RawDuration::Text(text) => text.parse(),
+    }
+  }
+}
+
+impl FromStr for HumanDuration {
+  type Err = String;
+
+  fn from_str(input: &str) -> Result<Self, Self::Err> {
+    let invalid = || format!("`{input}` is not a valid duration, expected something like 90s, 5m or 1h30m");
+
+    let text = input.trim();
+    if let Ok(seconds) = text.parse::<u64>() {
+      return Ok(Self(Duration::from_secs(seconds)));
+    }
+
+    let mut total = Duration::ZERO;
+    let mut chars = text.chars().peekable();
+
+    while chars.peek().is_some() {
+      let mut number = String::new();
+      while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        number.push(digit);
+      }
+
+      let mut unit = String::new();
+      while let Some(letter) = chars.next_if(char::is_ascii_alphabetic) {
+        unit.push(letter);
+      }
+
+      let value = number.parse::<u64>().map_err(|_| invalid())?;
+      let part = match unit.as_str() {
+        "ms" => Duration::from_millis(value),
+        "s" => Duration::from_secs(value),
+        "m" => Duration::from_secs(value.saturating_mul(60)),
+        "h" => Duration::from_secs(value.saturating_mul(3600)),
+        "d" => Duration::from_secs(value.saturating_mul(86400)),
+        _ => return Err(invalid()),
+      };
+
+      total = total.saturating_add(part);
+    }
+
+    if text.is_empty() {
+      return Err(invalid());
+    }
+
+    Ok(Self(total))
+  }
+}
+
+impl Display for HumanDuration {
+  fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+    let millis = self.0.as_millis();
+
+    for (unit, size) in [("d", 86_400_000), ("h", 3_600_000), ("m", 60_000), ("s", 1000)] {
+      if millis >= size && millis.checked_rem(size) == Some(0) {
+        return write!(f, "{}{unit}", millis.checked_div(size).unwrap_or_default());
+      }
+    }
+
+    write!(f, "{millis}ms")
+  }
+}
+
+impl From<HumanDuration> for String {
+  fn from(duration: HumanDuration) -> Self {
+    duration.to_string()
+  }
+}
@@ -0,0 +1,222 @@
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+use uzers::{get_group_by_gid, get_user_by_name, get_user_by_uid, os::unix::UserExt};
+
+use super::{ser::LaunchConfigRunAs, Options};
+
+/// The tool `run_as.elevate` runs a command through to switch to another user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Elevate {
+  /// The first of the other tools that is installed, in the order they are listed here.
+  #[default]
+  Auto,
+  Sudo,
+  Doas,
+  Run0,
+  Pkexec,
+  Su,
+}
+
+impl Display for Elevate {
+  fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+    write!(f, "{}", self.binary())
+  }
+}
+
+impl Elevate {
+  const DETECTED: [Self; 5] = [Self::Sudo, Self::Doas, Self::Run0, Self::Pkexec, Self::Su];
+
+  pub const fn binary(self) -> &'static str {
+    match self {
+      Self::Auto => "auto",
+      Self::Sudo => "sudo",
+      Self::Doas => "doas",
+      Self::Run0 => "run0",
+      Self::Pkexec => "pkexec",
+      Self::Su => "su",
+    }
+  }
+
+  /// Replaces `auto` with the first installed tool, and checks that any other tool is installed.
+  pub fn detect(self) -> Result<Self, String> {
+    if self == Self::Auto {
+      return Self::DETECTED.into_iter().find(|tool| tool.is_installed()).ok_or_else(|| {
+        let names = Self::DETECTED.map(Self::binary).join(", ");
+        format!("none of {names} is installed")
+      });
+    }
+
+    if self.is_installed() {
+      Ok(self)
+    } else {
+      Err(format!("<brightmagenta>{}</brightmagenta> is not installed", self.binary()))
+    }
+  }
+
+  fn is_installed(self) -> bool {
+    std::env::var_os("PATH").is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(self.binary()).is_file()))
+  }
+
+  /// Prefixes `argv` with the tool and the arguments it expects for the user, group and login shell of `run_as`.
+  pub fn wrap(self, run_as: &LaunchConfigRunAs, argv: Vec<String>) -> Result<Vec<String>, String> {
+    if self == Self::Auto {
+      return self.detect().and_then(|tool| tool.wrap(run_as, argv));
+    }
+
+    let user = run_as.user.as_deref().map(|user| self.user_arg(user));
+    let group = run_as.group.as_deref().map(|group| self.group_arg(group));
+    let login = run_as.login_shell.unwrap_or(false);
+
+    if group.is_some() && matches!(self, Self::Doas | Self::Pkexec) {
+      return Err(format!("<brightmagenta>{self}</brightmagenta> cannot switch the group, remove <brightblue>run_as.group</brightblue> or use another tool"));
+    }
+
+    // the tools without a login mode of their own run the command through the login shell of the user
+    let program = match (self, login) {
+      (Self::Doas | Self::Run0 | Self::Pkexec, true) => {
+        let mut wrapped = vec![login_shell(run_as.user.as_deref()), "-l".to_owned(), "-c".to_owned(), "exec \"$0\" \"$@\"".to_owned()];
+        wrapped.extend(argv);
+        wrapped
+      },
+      _ => argv,
+    };
+
+    let mut args = vec![self.binary().to_owned()];
+    match self {
+      Self::Auto => {},
+      Self::Sudo => {
+        if login {
+          args.push("-i".to_owned());
+        }
+
+        args.extend(user.into_iter().flat_map(|name| ["-u".to_owned(), name]));
+        args.extend(group.into_iter().flat_map(|name| ["-g".to_owned(), name]));
+        args.push("--".to_owned());
+        args.extend(program);
+      },
+      Self::Doas => {
+        args.extend(user.into_iter().flat_map(|name| ["-u".to_owned(), name]));
+        args.push("--".to_owned());
+        args.extend(program);
+      },
+      Self::Run0 => {
+        args.extend(user.map(|name| format!("--user={name}")));
+        args.extend(group.map(|name| format!("--group={name}")));
+        args.push("--".to_owned());
+        args.extend(program);
+      },
+      // pkexec takes the first argument that is not one of its own options as the program, it does not know `--`
+      Self::Pkexec => {
+        args.extend(user.into_iter().flat_map(|name| ["--user".to_owned(), name]));
+        args.extend(program);
+      },
+      // su passes a single script to the shell of the user
+      Self::Su => {
+        if login {
+          args.push("-l".to_owned());
+        }
+
+        args.extend(group.into_iter().flat_map(|name| ["-g".to_owned(), name]));
+        args.push("-c".to_owned());
+        args.push(program.iter().map(|arg| Options::quote_arg(arg)).collect::<Vec<String>>().join(" "));
+        args.push(user.unwrap_or_else(|| "root".to_owned()));
+      },
+    }
+
+    Ok(args)
+  }
+
+  /// sudo takes ids as `#1000`, the other tools need names, except run0 which takes both.
+  fn user_arg(self, user: &str) -> String {
+    match (self, user.parse::<u32>()) {
+      (Self::Sudo, Ok(uid)) => format!("#{uid}"),
+      (Self::Doas | Self::Pkexec | Self::Su, Ok(uid)) => get_user_by_uid(uid).map_or_else(|| user.to_owned(), |entry| entry.name().to_string_lossy().to_string()),
+      _ => user.to_owned(),
+    }
+  }
+
+  fn group_arg(self, group: &str) -> String {
+    match (self, group.parse::<u32>()) {
+      (Self::Sudo, Ok(gid)) => format!("#{gid}"),
+      (Self::Su, Ok(gid)) => get_group_by_gid(gid).map_or_else(|| group.to_owned(), |entry| entry.name().to_string_lossy().to_string()),
+      _ => group.to_owned(),
+    }
+  }
+}
+
+/// The login shell of `user`, root if there is none, falling back to `/bin/sh`.
+fn login_shell(user: Option<&str>) -> String {
+  let name = user.unwrap_or("root");
+  let entry = match name.parse::<u32>() {
+    Ok(uid) => get_user_by_uid(uid),
+    Err(_) => get_user_by_name(name),
+  };
+
+  entry.map(|account| account.shell().to_path_buf())
+    .filter(|shell| shell.is_file())
+    .map_or_else(|| "/bin/sh".to_owned(), |shell| shell.display().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{login_shell, Elevate};
+  use crate::operations::run::ser::LaunchConfigRunAs;
+
+  const LOGIN: &str = "exec \"$0\" \"$@\"";
+
+  fn wrap(tool: Elevate, user: Option<&str>, group: Option<&str>, login: bool) -> Result<Vec<String>, String> {
+    let run_as = LaunchConfigRunAs {
+      user: user.map(str::to_owned),
+      group: group.map(str::to_owned),
+      login_shell: Some(login),
+      ..LaunchConfigRunAs::default()
+    };
+
+    tool.wrap(&run_as, vec!["printf".to_owned(), "%s\n".to_owned(), "a b".to_owned()])
+  }
+
+  fn argv(args: &[&str]) -> Result<Vec<String>, String> {
+    Ok(args.iter().map(|arg| (*arg).to_owned()).collect())
+  }
+
+  #[test]
+  fn sudo_passes_ids_with_a_hash() {
+    assert_eq!(wrap(Elevate::Sudo, None, None, false), argv(&["sudo", "--", "printf", "%s\n", "a b"]));
+    assert_eq!(wrap(Elevate::Sudo, Some("0"), Some("0"), false), argv(&["sudo", "-u", "#0", "-g", "#0", "--", "printf", "%s\n", "a b"]));
+    assert_eq!(wrap(Elevate::Sudo, Some("root"), None, true), argv(&["sudo", "-i", "-u", "root", "--", "printf", "%s\n", "a b"]));
+  }
+
+  #[test]
+  fn doas_takes_names_and_no_group() {
+    let shell = login_shell(Some("0"));
+
+    assert_eq!(wrap(Elevate::Doas, Some("0"), None, false), argv(&["doas", "-u", "root", "--", "printf", "%s\n", "a b"]));
+    assert_eq!(wrap(Elevate::Doas, None, None, true), argv(&["doas", "--", &shell, "-l", "-c", LOGIN, "printf", "%s\n", "a b"]));
+    assert!(wrap(Elevate::Doas, None, Some("root"), false).is_err());
+  }
+
+  #[test]
+  fn run0_takes_ids_and_names() {
+    let shell = login_shell(Some("root"));
+
+    assert_eq!(wrap(Elevate::Run0, Some("0"), Some("root"), false), argv(&["run0", "--user=0", "--group=root", "--", "printf", "%s\n", "a b"]));
+    assert_eq!(wrap(Elevate::Run0, Some("root"), None, true), argv(&["run0", "--user=root", "--", &shell, "-l", "-c", LOGIN, "printf", "%s\n", "a b"]));
+  }
+
+  #[test]
+  fn pkexec_has_no_separator() {
+    let shell = login_shell(None);
+
+    assert_eq!(wrap(Elevate::Pkexec, None, None, false), argv(&["pkexec", "printf", "%s\n", "a b"]));
+    assert_eq!(wrap(Elevate::Pkexec, Some("0"), None, true), argv(&["pkexec", "--user", "root", &shell, "-l", "-c", LOGIN, "printf", "%s\n", "a b"]));
+    assert!(wrap(Elevate::Pkexec, None, Some("0"), false).is_err());
+  }
+
+  #[test]
+  fn su_quotes_the_command_into_a_script() {
+    assert_eq!(wrap(Elevate::Su, None, None, false), argv(&["su", "-c", "printf '%s\n' 'a b'", "root"]));
+    assert_eq!(wrap(Elevate::Su, Some("0"), Some("0"), true), argv(&["su", "-l", "-g", "root", "-c", "printf '%s\n' 'a b'", "root"]));
+  }
+}
@@ -0,0 +1,228 @@
+use std::{
+  io::{BufRead, BufReader, IsTerminal, Read, Write},
+  os::unix::process::CommandExt,
+  process::{Child, Command, ExitStatus, Stdio},
+  sync::atomic::{AtomicBool, Ordering},
+  thread::{sleep, JoinHandle},
+  time::{Duration, Instant},
+};
+
+use std_v2::{
+  console::CONSOLE,
+  string::{ansi::Effect, StringV2},
+  struct_gen,
+};
+
+use super::{
+  signals::{exit_code, signal_group},
+  POLL_INTERVAL,
+};
+use crate::operations::escape_markup;
+
+/// Names of the groups that are currently being run, to detect groups that contain themselves.
+const GROUP_STACK_VAR: &str = "CTR_GROUP_STACK";
+const GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_interrupt(_: libc::c_int) {
+  INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Records SIGINT, SIGTERM and SIGHUP instead of exiting, so that ctr can stop the processes it started first,
+/// also when its terminal is closed.
+/// Commands started afterwards still get the default handlers.
+pub fn catch_interrupts() {
+  let handler: extern "C" fn(libc::c_int) = on_interrupt;
+
+  // SAFETY: the handler only stores to an atomic
+  #[allow(clippy::as_conversions, clippy::fn_to_numeric_cast_any)]
+  unsafe {
+    libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+    libc::signal(libc::SIGTERM, handler as libc::sighandler_t);
+    libc::signal(libc::SIGHUP, handler as libc::sighandler_t);
+  }
+}
+
+pub fn interrupted() -> bool {
+  INTERRUPTED.load(Ordering::SeqCst)
+}
+
+struct_gen! {
+  /// Presets that are run in parallel, each as its own `ctr run <member>`.
+  pub struct Group {
+    pub let name: String = String::new();
+    pub let members: Vec<String> = Vec::new();
+    pub let fail_fast: bool = false;
+    /// Flags passed on to every member, like `--local`.
+    pub let flags: Vec<String> = Vec::new();
+  }
+
+  mod implementation {
+    /// Runs every member and waits for all of them. Returns the exit code of the first member that failed.
+    pub fn run(self: &Self) -> i32 {
+      let stack = std::env::var(GROUP_STACK_VAR).unwrap_or_default();
+      let mut names = stack.split(':').filter(|name| !name.is_empty()).map(str::to_owned).collect::<Vec<String>>();
+      if let Some(member) = self.members.iter().find(|member| names.contains(member) || **member == self.name) {
+        CONSOLE.exit(format!("The group <brightmagenta>{}</brightmagenta> contains itself through <brightmagenta>{member}</brightmagenta>", self.name));
+      }
+
+      names.push(self.name.clone());
+
+      // members run in their own process groups, so Ctrl-C only reaches ctr, which then stops all of them
+      catch_interrupts();
+
+      let executable = std::env::current_exe().unwrap_or_else(|err| CONSOLE.exit(format!("Failed to start the group: {err}")));
+      let width = self.members.iter().map(String::len).max().unwrap_or(0);
+
+      let mut members = vec![];
+      let mut tees = vec![];
+      for (index, member) in self.members.iter().enumerate() {
+        let mut command = Command::new(&executable);
+        command.arg("run").args(&self.flags).arg(member)
+          .env(GROUP_STACK_VAR, names.join(":"))
+          .stdin(Stdio::null())
+          .stdout(Stdio::piped())
+          .stderr(Stdio::piped())
+          .process_group(0);
+
+        let mut child = match command.spawn() {
+          Ok(child) => child,
+          Err(err) => {
+            CONSOLE.error(format!("Failed to start <brightmagenta>{member}</brightmagenta>: {err}"));
+            Self::stop_all(&mut members);
+            return 1_i32;
+          }
+        };
+
+        if let Some(stdout) = child.stdout.take() {
+          tees.push(forward(stdout, std::io::stdout(), Self::prefix(member, index, width, std::io::stdout().is_terminal()), |_| {}));
+        }
+
+        if let Some(stderr) = child.stderr.take() {
+          tees.push(forward(stderr, std::io::stderr(), Self::prefix(member, index, width, std::io::stderr().is_terminal()), |_| {}));
+        }
+
+        members.push((member.to_owned(), child, None::<ExitStatus>));
+      }
+
+      let mut code: Option<i32> = None;
+      while members.iter().any(|(_, _, status)| status.is_none()) {
+        if interrupted() {
+          CONSOLE.warn(format!("Stopping the group <brightmagenta>{}</brightmagenta>", self.name));
+          Self::stop_all(&mut members);
+          code = code.or(Some(130_i32));
+          break;
+        }
+
+        let mut failed = false;
+        for (member, child, status) in members.iter_mut().filter(|(_, _, status)| status.is_none()) {
+          let Ok(Some(exit_status)) = child.try_wait() else {
+            continue;
+          };
+
+          *status = Some(exit_status);
+          if !exit_status.success() {
+            let member_code = exit_code(exit_status);
+            CONSOLE.warn(format!("<brightmagenta>{member}</brightmagenta> exited with code {member_code}"));
+            code = code.or(Some(member_code));
+            failed = true;
+          }
+        }
+
+        if failed && self.fail_fast {
+          Self::stop_all(&mut members);
+          break;
+        }
+
+        sleep(POLL_INTERVAL);
+      }
+
+      for tee in tees {
+        let _ = tee.join();
+      }
+
+      code.unwrap_or(0_i32)
+    }
+
+    /// Sends SIGTERM to every member that is still running, followed by SIGKILL after the grace period.
+    fn stop_all(members: &mut [(String, Child, Option<ExitStatus>)]) {
+      for (_, child, _) in members.iter().filter(|(_, _, status)| status.is_none()) {
+        signal_group(child, libc::SIGTERM);
+      }
+
+      let kill_at = Instant::now().checked_add(GRACE_PERIOD).unwrap_or_else(Instant::now);
+      for (member, child, status) in members.iter_mut().filter(|(_, _, status)| status.is_none()) {
+        while Instant::now() < kill_at {
+          if let Ok(Some(exit_status)) = child.try_wait() {
+            *status = Some(exit_status);
+            break;
+          }
+
+          sleep(POLL_INTERVAL);
+        }
+
+        if status.is_none() {
+          CONSOLE.warn(format!("<brightmagenta>{member}</brightmagenta> is still running, sending <yellow>SIGKILL</yellow>"));
+          signal_group(child, libc::SIGKILL);
+          *status = child.wait().ok();
+        }
+      }
+    }
+
+    /// A `[name]` prefix, padded so that the output of all members lines up.
+    /// It is only colored if the output goes to a terminal.
+    pub fn prefix(member: &str, index: usize, width: usize, colored: bool) -> String {
+      const COLORS: [Effect; 10] = [
+        Effect::Cyan,
+        Effect::Magenta,
+        Effect::Yellow,
+        Effect::Green,
+        Effect::Blue,
+        Effect::BrightCyan,
+        Effect::BrightMagenta,
+        Effect::BrightYellow,
+        Effect::BrightGreen,
+        Effect::BrightBlue,
+      ];
+
+      let padding = " ".repeat(width.saturating_sub(member.len()));
+      if !colored {
+        return format!("[{member}]{padding} ");
+      }
+
+      let color = index.checked_rem(COLORS.len()).and_then(|index| COLORS.get(index)).cloned().unwrap_or(Effect::Cyan);
+      let mut prefix = StringV2::from(format!("[{}]", escape_markup(member)));
+      prefix.push_effect(color);
+
+      format!("{prefix}{padding} ")
+    }
+  }
+}
+
+/// Copies `source` to `output` line by line, with `prefix` in front of every line.
+/// Every line is also passed to `on_line`.
+pub fn forward<R, W, F>(source: R, mut output: W, prefix: String, on_line: F) -> JoinHandle<()>
+where
+  R: Read + Send + 'static,
+  W: Write + Send + 'static,
+  F: Fn(&[u8]) + Send + 'static,
+{
+  std::thread::spawn(move || {
+    let mut reader = BufReader::new(source);
+    let mut line = vec![];
+
+    while reader.read_until(b'\n', &mut line).is_ok_and(|read| read > 0) {
+      on_line(&line);
+
+      // written at once, so that lines of different members do not interleave
+      let mut entry = prefix.as_bytes().to_vec();
+      entry.append(&mut line);
+      if !entry.ends_with(b"\n") {
+        entry.push(b'\n');
+      }
+
+      let _ = output.write_all(&entry).and_then(|()| output.flush());
+    }
+  })
+}
@@ -0,0 +1,190 @@
+use std::{
+  fs::{self, OpenOptions},
+  io::Write,
+  os::fd::AsRawFd,
+  path::PathBuf,
+  sync::LazyLock,
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use std_v2::{console::CONSOLE, env::consts::CTR_CONFIG_DIR, lazy_var, struct_gen};
+
+lazy_var!(pub HISTORY_DIR<PathBuf> {
+  CTR_CONFIG_DIR.join("history")
+});
+
+/// Size of the history in bytes after which the oldest entries are dropped, unless set with `ctr history enable --max-size`.
+pub const DEFAULT_MAX_SIZE: u64 = 1 << 20_u32;
+
+/// Milliseconds since the unix epoch.
+pub fn now_millis() -> u64 {
+  SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| u64::try_from(duration.as_millis()).unwrap_or(u64::MAX))
+}
+
+struct_gen! {
+  /// Whether runs are recorded at all. Stored as `settings.json` in the history directory.
+  pub struct HistorySettings use Serialize, Deserialize, Clone {
+    pub let enabled: bool = false;
+    pub let max_size: u64 = DEFAULT_MAX_SIZE;
+  }
+
+  mod constructors {
+    pub fn settings_path() -> PathBuf {
+      HISTORY_DIR.join("settings.json")
+    }
+
+    pub fn load() -> Self {
+      fs::read_to_string(Self::settings_path()).ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+    }
+  }
+
+  mod implementation {
+    pub fn save(self: &Self) -> std::io::Result<()> {
+      fs::create_dir_all(&*HISTORY_DIR)?;
+      fs::write(Self::settings_path(), serde_json::to_string_pretty(self).map_err(std::io::Error::other)?)
+    }
+  }
+}
+
+struct_gen! {
+  /// A recorded `ctr run`. Stored as one line of `runs.jsonl`, oldest first.
+  pub struct HistoryEntry use Serialize, Deserialize, Clone {
+    pub let id: u64 = 0;
+    pub let preset: Option<String> = None;
+    pub let command: String = String::new();
+    pub let argv: Vec<String> = Vec::new();
+    pub let cwd: PathBuf = PathBuf::new();
+    /// Milliseconds since the unix epoch.
+    pub let started: u64 = 0;
+    /// `None` for daemonized runs, which ctr does not wait for.
+    pub let ended: Option<u64> = None;
+    pub let exit_code: Option<i32> = None;
+    pub let daemonized: bool = false;
+  }
+
+  mod constructors {
+    pub fn entries_path() -> PathBuf {
+      HISTORY_DIR.join("runs.jsonl")
+    }
+
+    /// Loads all entries, skipping lines that cannot be parsed.
+    pub fn load_all() -> Vec<Self> {
+      fs::read_to_string(Self::entries_path()).unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+    }
+
+    /// Loads the entry `id`, or exits if there is none.
+    pub fn find(id: &Option<String>) -> Self {
+      let id = id.as_deref().unwrap_or_else(|| CONSOLE.exit("No history entry specified"));
+      let number = id.parse::<u64>().unwrap_or_else(|_| CONSOLE.exit(format!("<brightmagenta>{id}</brightmagenta> is not a valid history id")));
+
+      Self::load_all().into_iter().find(|entry| entry.id == number)
+        .unwrap_or_else(|| CONSOLE.exit(format!("There is no history entry <brightmagenta>{id}</brightmagenta>")))
+    }
+  }
+
+  mod implementation {
+    /// Appends the entry with the next free id, if the history is enabled.
+    /// Only warns on failure, since the history must never get in the way of a run.
+    pub fn record(self: &mut Self) {
+      let settings = HistorySettings::load();
+      if !settings.enabled {
+        return;
+      }
+
+      if let Err(err) = self.append(settings.max_size) {
+        CONSOLE.warn(format!("Failed to record the run in the history: {err}"));
+      }
+    }
+
+    /// Picks the next id and appends the entry, while holding a lock so that concurrent runs,
+    /// like the ones started by `depends_on`, cannot pick the same id.
+    fn append(self: &mut Self, max_size: u64) -> std::io::Result<()> {
+      fs::create_dir_all(&*HISTORY_DIR)?;
+
+      // a file of its own, since `truncate` replaces `runs.jsonl` and with it any lock on it
+      let lock = OpenOptions::new().create(true).append(true).open(HISTORY_DIR.join("runs.lock"))?;
+
+      // SAFETY: `flock` has no memory safety requirements, the lock is released when `lock` is closed
+      if unsafe { libc::flock(lock.as_raw_fd(), libc::LOCK_EX) } != 0_i32 {
+        return Err(std::io::Error::last_os_error());
+      }
+
+      self.id = Self::load_all().last().map_or(1, |last| last.id.saturating_add(1));
+      let line = serde_json::to_string(self).map_err(std::io::Error::other)?;
+
+      let mut file = OpenOptions::new().create(true).append(true).open(Self::entries_path())?;
+      file.write_all(format!("{line}\n").as_bytes())?;
+
+      Self::truncate(max_size)
+    }
+
+    /// Drops the oldest entries until the history fits into `max_size` bytes.
+    /// The newest entry is always kept, since the next id follows from it.
+    fn truncate(max_size: u64) -> std::io::Result<()> {
+      let path = Self::entries_path();
+      if fs::metadata(&path)?.len() <= max_size {
+        return Ok(());
+      }
+
+      let contents = fs::read_to_string(&path)?;
+      let mut size = u64::try_from(contents.len()).unwrap_or(u64::MAX);
+      let mut remaining = contents.lines().count();
+      let kept = contents.lines().skip_while(|line| {
+        let too_large = size > max_size && remaining > 1;
+        remaining = remaining.saturating_sub(1);
+        if too_large {
+          size = size.saturating_sub(u64::try_from(line.len()).unwrap_or(u64::MAX).saturating_add(1));
+        }
+
+        too_large
+      }).map(|line| format!("{line}\n")).collect::<String>();
+
+      let temporary = path.with_extension("jsonl.tmp");
+      fs::write(&temporary, kept)?;
+      fs::rename(temporary, path)
+    }
+
+    /// The preset, or the binary of a command that was run without one.
+    pub fn name(self: &Self) -> String {
+      self.preset.clone().unwrap_or_else(|| self.command.split_whitespace().next().unwrap_or_default().to_owned())
+    }
+
+    pub fn duration(self: &Self) -> Option<Duration> {
+      self.ended.map(|ended| Duration::from_millis(ended.saturating_sub(self.started)))
+    }
+
+    pub fn failed(self: &Self) -> bool {
+      self.exit_code.is_some_and(|code| code != 0_i32)
+    }
+  }
+}
+
+struct_gen! {
+  /// Which entries `ctr history` shows.
+  pub struct HistoryFilter {
+    pub let preset: Option<String> = None;
+    pub let failed: bool = false;
+    /// Only entries started at most this long ago.
+    pub let since: Option<Duration> = None;
+    /// Only entries started at least this long ago.
+    pub let until: Option<Duration> = None;
+  }
+
+  mod implementation {
+    pub fn matches(self: &Self, entry: &HistoryEntry) -> bool {
+      let now = now_millis();
+      let age = Duration::from_millis(now.saturating_sub(entry.started));
+
+      self.preset.as_ref().is_none_or(|preset| entry.preset.as_ref() == Some(preset))
+        && (!self.failed || entry.failed())
+        && self.since.is_none_or(|since| age <= since)
+        && self.until.is_none_or(|until| age >= until)
+    }
+  }
+}
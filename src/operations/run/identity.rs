@@ -0,0 +1,144 @@
+use std::path::PathBuf;
+
+use std_v2::struct_gen;
+use uzers::{get_group_by_name, get_user_by_name, get_user_by_uid, get_user_groups, os::unix::UserExt, User};
+
+/// Bits of the capabilities in `CapEff` of `/proc/self/status`.
+const CAP_SETGID: u32 = 6;
+const CAP_SETUID: u32 = 7;
+
+struct_gen! {
+  /// The user and groups a command runs as when `run_as` is used without `elevate`.
+  pub struct Identity use Clone {
+    pub let uid: u32 = 0;
+    pub let gid: u32 = 0;
+    /// The supplementary groups of the user, as `initgroups(3)` would set them.
+    pub let groups: Vec<u32> = Vec::new();
+    /// The user name, or the uid if it has no entry in the user database.
+    pub let name: String = String::new();
+    pub let home: Option<PathBuf> = None;
+    pub let shell: Option<PathBuf> = None;
+    /// Whether the command runs through the login shell of the user, see `run_as.login_shell`.
+    pub let login_shell: bool = false;
+  }
+
+  mod constructors {
+    /// Looks up `user` and `group`, given as names or ids. Without a user the current one is kept,
+    /// without a group the primary group of the user is used.
+    pub fn resolve(user: &Option<String>, group: &Option<String>, login_shell: bool) -> Result<Self, String> {
+      // SAFETY: `getuid` and `getgid` cannot fail
+      let (current_uid, current_gid) = unsafe { (libc::getuid(), libc::getgid()) };
+
+      let (uid, account) = match user {
+        Some(user) => match user.parse::<u32>() {
+          Ok(uid) => (uid, get_user_by_uid(uid)),
+          Err(_) => {
+            let account = get_user_by_name(user).ok_or_else(|| format!("there is no user <brightmagenta>{user}</brightmagenta>"))?;
+            (account.uid(), Some(account))
+          },
+        },
+        None => (current_uid, get_user_by_uid(current_uid)),
+      };
+
+      let gid = match group {
+        Some(group) => match group.parse::<u32>() {
+          Ok(gid) => gid,
+          Err(_) => get_group_by_name(group).ok_or_else(|| format!("there is no group <brightmagenta>{group}</brightmagenta>"))?.gid(),
+        },
+        None => account.as_ref().map_or(current_gid, User::primary_group_id),
+      };
+
+      let groups = account.as_ref()
+        .and_then(|entry| get_user_groups(entry.name(), gid))
+        .map_or_else(|| vec![gid], |groups| groups.iter().map(uzers::Group::gid).collect());
+
+      Ok(Self {
+        uid,
+        gid,
+        groups,
+        name: account.as_ref().map_or_else(|| uid.to_string(), |entry| entry.name().to_string_lossy().to_string()),
+        home: account.as_ref().map(|entry| entry.home_dir().to_path_buf()),
+        shell: account.as_ref().map(|entry| entry.shell().to_path_buf()),
+        login_shell,
+      })
+    }
+  }
+
+  mod implementation {
+    /// Whether ctr already runs as this user and group, in which case nothing has to be switched.
+    pub fn is_current(self: &Self) -> bool {
+      // SAFETY: `getuid` and `getgid` cannot fail
+      unsafe { self.uid == libc::getuid() && self.gid == libc::getgid() }
+    }
+
+    /// Checks up front that ctr is allowed to switch to the identity, and explains what is missing otherwise.
+    pub fn check_permission(self: &Self) -> Result<(), String> {
+      if self.is_current() {
+        return Ok(());
+      }
+
+      // SAFETY: `getuid` cannot fail
+      let current_uid = unsafe { libc::getuid() };
+      let capabilities = std::fs::read_to_string("/proc/self/status").ok()
+        .and_then(|status| status.lines().find_map(|line| line.strip_prefix("CapEff:").map(|hex| u64::from_str_radix(hex.trim(), 16).unwrap_or(0))))
+        .unwrap_or(0);
+      let has = |capability: u32| capabilities & (1_u64 << capability) != 0;
+
+      let mut missing = vec![];
+      if self.uid != current_uid && !has(CAP_SETUID) {
+        missing.push("CAP_SETUID");
+      }
+
+      if !has(CAP_SETGID) {
+        missing.push("CAP_SETGID");
+      }
+
+      if missing.is_empty() {
+        return Ok(());
+      }
+
+      let current = get_user_by_uid(current_uid).map_or_else(|| current_uid.to_string(), |user| user.name().to_string_lossy().to_string());
+      Err(format!(
+        "Running as <brightmagenta>{}</brightmagenta> requires {}, which <brightmagenta>{current}</brightmagenta> does not have. Run ctr as root, or set <brightblue>run_as.elevate</brightblue> to go through sudo or a similar tool",
+        self.name,
+        missing.join(" and ")
+      ))
+    }
+
+    /// `HOME`, `USER`, `LOGNAME` and `SHELL` of the user, set when switching to another user.
+    pub fn environment(self: &Self) -> Vec<(String, String)> {
+      let mut vars = vec![("USER".to_owned(), self.name.clone()), ("LOGNAME".to_owned(), self.name.clone())];
+
+      if let Some(ref home) = self.home {
+        vars.push(("HOME".to_owned(), home.display().to_string()));
+      }
+
+      if let Some(ref shell) = self.shell {
+        vars.push(("SHELL".to_owned(), shell.display().to_string()));
+      }
+
+      vars
+    }
+
+    /// Switches the calling process to the identity: the supplementary groups first, then the group and the user.
+    /// Runs between `fork` and `exec`, so it must not allocate.
+    pub fn apply(self: &Self) -> std::io::Result<()> {
+      macro_rules! check {
+        ($result:expr) => {
+          if ($result).is_negative() {
+            return Err(std::io::Error::last_os_error());
+          }
+        };
+      }
+
+      // SAFETY: `groups` is valid for reads of its length, the other calls take plain values
+      unsafe {
+        check!(libc::setgroups(self.groups.len(), self.groups.as_ptr()));
+        check!(libc::setgid(self.gid));
+        check!(libc::setuid(self.uid));
+      }
+
+      Ok(())
+    }
+  }
+}
@@ -0,0 +1,191 @@
+use std::{iter::Peekable, str::Chars};
+
+use std_v2::env::consts::{CTR_CONFIG_DIR, HOME};
+
+/// What kind of preset string is expanded, which decides what is left as it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Context {
+  /// Paths, names and other plain values.
+  Value,
+  /// Scripts run by a shell, which has its own expansions.
+  Command,
+}
+
+/// Expands variables in a preset string, looking them up with `vars`.
+///
+/// - `${VAR}` fails if `VAR` is not set
+/// - `${VAR:-default}` falls back to `default` if `VAR` is unset or empty
+/// - `${VAR:?message}` fails with `message` if `VAR` is unset or empty
+/// - `$VAR` is expanded as well
+/// - `$$` is a literal `$`
+/// - a leading `~` is the home directory
+///
+/// In a [`Context::Command`] only `${...}` is expanded, and only if `VAR` is known, everything else is left to the shell:
+/// `$VAR`, `$$`, variables of the script itself like `${f}` in a loop, and expansions like `${1}` or `${VAR%%.*}`.
+pub fn interpolate(input: &str, vars: &dyn Fn(&str) -> Option<String>, context: Context) -> Result<String, String> {
+  let mut output = String::new();
+  let mut chars = input.chars().peekable();
+
+  if chars.peek() == Some(&'~') {
+    let mut rest = input.chars().skip(1);
+    if rest.next().is_none_or(|c| c == '/') {
+      chars.next();
+      output.push_str(&HOME.to_string_lossy());
+    }
+  }
+
+  while let Some(c) = chars.next() {
+    if c != '$' {
+      output.push(c);
+      continue;
+    }
+
+    match chars.peek() {
+      Some('$') => {
+        chars.next();
+        output.push_str(if context == Context::Command { "$$" } else { "$" });
+      },
+      Some('{') => {
+        chars.next();
+        output.push_str(&expand_braced(&mut chars, vars, context)?);
+      },
+      Some(next) if context == Context::Value && (next.is_ascii_alphabetic() || *next == '_') => {
+        let name = name(&mut chars);
+        output.push_str(&vars(&name).ok_or_else(|| format!("`{name}` is not set"))?);
+      },
+      _ => output.push('$'),
+    }
+  }
+
+  Ok(output)
+}
+
+fn name(chars: &mut Peekable<Chars>) -> String {
+  let mut name = String::new();
+  while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+    name.push(c);
+  }
+
+  name
+}
+
+/// Expands `${...}`, after the opening brace has been consumed.
+fn expand_braced(chars: &mut Peekable<Chars>, vars: &dyn Fn(&str) -> Option<String>, context: Context) -> Result<String, String> {
+  // the operand may contain nested `${...}`, so collect up to the matching brace
+  let mut body = String::new();
+  let mut depth: u32 = 0;
+  let terminated = loop {
+    match chars.next() {
+      Some('}') if depth == 0 => break true,
+      Some(c) => {
+        match c {
+          '{' => depth = depth.saturating_add(1),
+          '}' => depth = depth.saturating_sub(1),
+          _ => {},
+        }
+        body.push(c);
+      },
+      None => break false,
+    }
+  };
+
+  let verbatim = || if terminated { format!("${{{body}}}") } else { format!("${{{body}") };
+  let leave_to_shell = |err: String| if context == Context::Command { Ok(verbatim()) } else { Err(err) };
+
+  if !terminated {
+    return leave_to_shell(format!("unterminated `{}`", verbatim()));
+  }
+
+  let mut body_chars = body.chars().peekable();
+  let name = name(&mut body_chars);
+  if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+    return leave_to_shell(format!("expected a variable name after `${{` in `{}`", verbatim()));
+  }
+
+  let operand = body_chars.collect::<String>();
+  let value = vars(&name);
+  let non_empty = value.clone().filter(|set| !set.is_empty());
+  let mut operand_chars = operand.chars();
+  match (operand_chars.next(), operand_chars.next()) {
+    (None, _) => value.map_or_else(|| leave_to_shell(format!("`{name}` is not set")), Ok),
+    (Some(':'), Some('-')) => match non_empty {
+      Some(set) => Ok(set),
+      None if context == Context::Command => Ok(verbatim()),
+      None => interpolate(operand_chars.as_str(), vars, context),
+    },
+    (Some(':'), Some('?')) => match non_empty {
+      Some(set) => Ok(set),
+      None => {
+        let message = operand_chars.as_str();
+        leave_to_shell(if message.is_empty() { format!("`{name}` is not set") } else { format!("`{name}`: {message}") })
+      },
+    },
+    _ => leave_to_shell(format!("unsupported expansion `{}`", verbatim())),
+  }
+}
+
+/// The built-in variables, followed by the environment of ctr.
+pub fn lookup(name: &str, preset: &str) -> Option<String> {
+  match name {
+    "CTR_PRESET" => Some(preset.to_owned()),
+    "CTR_CONFIG_DIR" => Some(CTR_CONFIG_DIR.to_string_lossy().to_string()),
+    "HOME" => Some(HOME.to_string_lossy().to_string()),
+    _ => std::env::var(name).ok(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{interpolate, Context};
+
+  fn vars(name: &str) -> Option<String> {
+    match name {
+      "FOO" => Some("bar".to_owned()),
+      "EMPTY" => Some(String::new()),
+      _ => None,
+    }
+  }
+
+  fn command(input: &str) -> Result<String, String> {
+    interpolate(input, &vars, Context::Command)
+  }
+
+  fn value(input: &str) -> Result<String, String> {
+    interpolate(input, &vars, Context::Value)
+  }
+
+  #[test]
+  fn command_leaves_script_variables_to_the_shell() {
+    let script = r#"for f in a b; do echo "${f}"; done"#;
+    assert_eq!(command(script), Ok(script.to_owned()));
+  }
+
+  #[test]
+  fn command_expands_known_variables() {
+    assert_eq!(command("echo ${FOO}"), Ok("echo bar".to_owned()));
+    assert_eq!(command("echo ${EMPTY:-fallback} ${FOO:-fallback}"), Ok("echo ${EMPTY:-fallback} bar".to_owned()));
+  }
+
+  #[test]
+  fn command_leaves_bare_variables_and_pid() {
+    assert_eq!(command("echo $FOO $X pid=$$"), Ok("echo $FOO $X pid=$$".to_owned()));
+  }
+
+  #[test]
+  fn command_leaves_unsupported_expansions() {
+    assert_eq!(command("echo ${1} ${FOO%%r} ${#FOO} ${X:?missing} ${unterminated"), Ok("echo ${1} ${FOO%%r} ${#FOO} ${X:?missing} ${unterminated".to_owned()));
+  }
+
+  #[test]
+  fn value_expands_everything() {
+    assert_eq!(value("~/$FOO/${X:-${FOO}}/$$"), Ok(format!("{}/bar/bar/$", std_v2::env::consts::HOME.to_string_lossy())));
+  }
+
+  #[test]
+  fn value_fails_on_unknown_variables() {
+    assert!(value("${X}").is_err());
+    assert!(value("$X").is_err());
+    assert!(value("${FOO%%r}").is_err());
+    assert_eq!(value("${X:?missing}"), Err("`X`: missing".to_owned()));
+  }
+}
@@ -0,0 +1,176 @@
+use std::{
+  fs,
+  path::PathBuf,
+  sync::LazyLock,
+  thread::sleep,
+  time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use std_v2::{console::CONSOLE, env::consts::CTR_CONFIG_DIR, lazy_var, string::StringV2, struct_gen};
+use sysinfo::{Pid, ProcessesToUpdate, System};
+
+use super::{duration::HumanDuration, signals::Signal};
+
+lazy_var!(pub JOBS_DIR<PathBuf> {
+  CTR_CONFIG_DIR.join("jobs")
+});
+
+/// Set for the command of a job, to its id.
+pub const JOB_ID_VAR: &str = "CTR_JOB_ID";
+/// Set by `ctr restart` for the `ctr run` it starts, so that the job keeps its id.
+pub const RESTART_ID_VAR: &str = "CTR_RESTART_JOB_ID";
+
+/// Seconds since the unix epoch.
+pub fn now() -> u64 {
+  SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs())
+}
+
+/// Start time of `pid` as reported by the system, used to tell a job apart from a process that reused its pid.
+pub fn process_start_time(pid: u32) -> Option<u64> {
+  let process_id = Pid::from_u32(pid);
+  let mut system = System::new();
+  system.refresh_processes(ProcessesToUpdate::Some(&[process_id]), true);
+  system.process(process_id).map(|process| process.start_time())
+}
+
+struct_gen! {
+  /// A daemonized command. Stored as `<id>.json`, next to its `<id>.pid` and `<id>.log`.
+  pub struct Job use Serialize, Deserialize, Clone {
+    pub let id: String = String::new();
+    pub let preset: Option<String> = None;
+    pub let pid: u32 = 0;
+    pub let started: u64 = 0;
+    pub let process_start: Option<u64> = None;
+    pub let command: String = String::new();
+    pub let invoked_from: PathBuf = PathBuf::new();
+    pub let argv: Vec<String> = Vec::new();
+    /// See `general.stop_signal` and `general.stop_grace`, `None` for jobs recorded before they existed.
+    pub let stop_signal: Option<Signal> = None;
+    pub let stop_grace: Option<HumanDuration> = None;
+  }
+
+  mod paths {
+    pub fn state_path(id: &str) -> PathBuf {
+      JOBS_DIR.join(format!("{id}.json"))
+    }
+
+    pub fn pid_path(id: &str) -> PathBuf {
+      JOBS_DIR.join(format!("{id}.pid"))
+    }
+
+    pub fn log_path(id: &str) -> PathBuf {
+      JOBS_DIR.join(format!("{id}.log"))
+    }
+  }
+
+  mod constructors {
+    pub fn load(id: &str) -> Option<Self> {
+      let contents = fs::read_to_string(Self::state_path(id)).ok()?;
+      serde_json::from_str(&contents).ok()
+    }
+
+    pub fn load_all() -> Vec<Self> {
+      let mut jobs = match fs::read_dir(&*JOBS_DIR) {
+        Ok(entries) => entries
+          .filter_map(|entry| entry.ok())
+          .map(|entry| entry.path())
+          .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+          .filter_map(|path| path.file_stem().and_then(|stem| Self::load(&stem.to_string_lossy())))
+          .collect::<Vec<Self>>(),
+        Err(_) => vec![],
+      };
+
+      jobs.sort_by_key(|job| job.started);
+      jobs
+    }
+
+    /// Loads the job `id`, or exits with a suggestion if there is none.
+    pub fn find(id: &Option<String>) -> Self {
+      let id = id.as_deref().unwrap_or_else(|| CONSOLE.exit("No job specified"));
+
+      Self::load(id).unwrap_or_else(|| {
+        let ids = Self::load_all().into_iter().map(|job| job.id).collect::<Vec<String>>();
+        CONSOLE.exit(match StringV2::from(id).nearest(ids) {
+          Some(suggestion) => format!("There is no job <brightmagenta>{id}</brightmagenta>. Did you mean <brightmagenta>{suggestion}</brightmagenta>?"),
+          None => format!("There is no job <brightmagenta>{id}</brightmagenta>"),
+        })
+      })
+    }
+
+    /// Returns `name`, or `name-2`, `name-3`, ... if a running job already uses it.
+    pub fn unique_id(name: &str) -> String {
+      let taken = Self::load_all().into_iter().filter(|job| job.is_alive()).map(|job| job.id).collect::<Vec<String>>();
+
+      let mut id = name.to_owned();
+      let mut suffix: u32 = 1;
+      while taken.contains(&id) {
+        suffix = suffix.saturating_add(1);
+        id = format!("{name}-{suffix}");
+      }
+
+      id
+    }
+  }
+
+  mod implementation {
+    pub fn save(self: &Self) -> std::io::Result<()> {
+      fs::create_dir_all(&*JOBS_DIR)?;
+      fs::write(Self::state_path(&self.id), serde_json::to_string_pretty(self).map_err(std::io::Error::other)?)?;
+      fs::write(Self::pid_path(&self.id), format!("{}\n", self.pid))
+    }
+
+    /// Removes the state record and pidfile. The log is kept.
+    pub fn remove(self: &Self) {
+      for path in [Self::state_path(&self.id), Self::pid_path(&self.id)] {
+        if let Err(err) = fs::remove_file(&path) {
+          if err.kind() != std::io::ErrorKind::NotFound {
+            CONSOLE.warn(format!("Failed to remove {}: {err}", path.display()));
+          }
+        }
+      }
+    }
+
+    pub fn is_alive(self: &Self) -> bool {
+      match process_start_time(self.pid) {
+        Some(start_time) => self.process_start.is_none_or(|recorded| recorded == start_time),
+        None => false,
+      }
+    }
+
+    pub fn uptime(self: &Self) -> Duration {
+      Duration::from_secs(now().saturating_sub(self.started))
+    }
+
+    /// Sends `signal` to the process group of the job.
+    pub fn signal(self: &Self, signal: i32) -> bool {
+      match i32::try_from(self.pid).ok().and_then(i32::checked_neg) {
+        // SAFETY: `kill` has no memory safety requirements
+        Some(group) => unsafe { libc::kill(group, signal) == 0 },
+        None => false,
+      }
+    }
+
+    /// Sends the stop signal of the job, followed by SIGKILL if it is still running after the grace period, and removes the record.
+    /// `grace` overrides the grace period of the preset.
+    pub fn stop(self: &Self, grace: Option<Duration>) {
+      let grace_period = grace.or(self.stop_grace.map(|stop_grace| stop_grace.0)).unwrap_or(Duration::from_secs(10));
+
+      if self.is_alive() {
+        self.signal(self.stop_signal.unwrap_or_default().0);
+
+        let kill_at = Instant::now().checked_add(grace_period).unwrap_or_else(Instant::now);
+        while self.is_alive() && Instant::now() < kill_at {
+          sleep(Duration::from_millis(100));
+        }
+
+        if self.is_alive() {
+          CONSOLE.warn(format!("<brightmagenta>{}</brightmagenta> is still running, sending <yellow>SIGKILL</yellow>", self.id));
+          self.signal(libc::SIGKILL);
+        }
+      }
+
+      self.remove();
+    }
+  }
+}
@@ -0,0 +1,288 @@
+use std::{
+  fmt::{Display, Formatter},
+  str::FromStr,
+};
+
+use serde::{Deserialize, Serialize, Serializer};
+use std_v2::struct_gen;
+
+/// `ioprio_set(2)` targets a process when `which` is `IOPRIO_WHO_PROCESS`.
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+const IOPRIO_CLASS_SHIFT: u32 = 13;
+
+/// The type of the `RLIMIT_*` constants, which glibc declares as an enum and other C libraries as an int.
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+type Resource = libc::c_int;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawValue {
+  Number(u64),
+  Text(String),
+}
+
+/// A resource limit, either a plain number, a size like `512M` or `4G`, or `unlimited`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "RawValue")]
+pub struct LimitValue(pub libc::rlim_t);
+
+impl TryFrom<RawValue> for LimitValue {
+  type Error = String;
+
+  fn try_from(raw: RawValue) -> Result<Self, Self::Error> {
+    match raw {
+      RawValue::Number(number) => Ok(Self(number)),
+      RawValue::Text(text) => text.parse(),
+    }
+  }
+}
+
+impl FromStr for LimitValue {
+  type Err = String;
+
+  fn from_str(input: &str) -> Result<Self, Self::Err> {
+    let text = input.trim();
+    if ["unlimited", "infinity"].contains(&text) {
+      return Ok(Self(libc::RLIM_INFINITY));
+    }
+
+    let (number, multiplier) = match text.chars().last() {
+      Some('K' | 'k') => (text.trim_end_matches(['K', 'k']), 1_u64 << 10_u32),
+      Some('M' | 'm') => (text.trim_end_matches(['M', 'm']), 1_u64 << 20_u32),
+      Some('G' | 'g') => (text.trim_end_matches(['G', 'g']), 1_u64 << 30_u32),
+      Some('T' | 't') => (text.trim_end_matches(['T', 't']), 1_u64 << 40_u32),
+      _ => (text, 1_u64),
+    };
+
+    number.parse::<u64>()
+      .map(|value| Self(value.saturating_mul(multiplier)))
+      .map_err(|_| format!("`{input}` is not a valid limit, expected a number, a size like 4G or `unlimited`"))
+  }
+}
+
+impl Display for LimitValue {
+  fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+    if self.0 == libc::RLIM_INFINITY {
+      write!(f, "unlimited")
+    } else {
+      write!(f, "{}", self.0)
+    }
+  }
+}
+
+impl Serialize for LimitValue {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    if self.0 == libc::RLIM_INFINITY {
+      serializer.serialize_str("unlimited")
+    } else {
+      serializer.serialize_u64(self.0)
+    }
+  }
+}
+
+/// A set of CPUs, written as a list like `[0, 1]` or a string like `"0-3,6"`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "RawCpuList", into = "String")]
+pub struct CpuList(pub Vec<usize>);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawCpuList {
+  List(Vec<usize>),
+  Text(String),
+}
+
+/// The highest cpu number that fits into a `cpu_set_t`.
+const MAX_CPU: usize = 1023;
+
+impl TryFrom<RawCpuList> for CpuList {
+  type Error = String;
+
+  fn try_from(raw: RawCpuList) -> Result<Self, Self::Error> {
+    match raw {
+      RawCpuList::List(cpus) if cpus.iter().all(|cpu| *cpu <= MAX_CPU) => Ok(Self(cpus)),
+      RawCpuList::List(_) => Err(format!("cpu numbers cannot be higher than {MAX_CPU}")),
+      RawCpuList::Text(text) => text.parse(),
+    }
+  }
+}
+
+impl FromStr for CpuList {
+  type Err = String;
+
+  fn from_str(input: &str) -> Result<Self, Self::Err> {
+    let invalid = || format!("`{input}` is not a valid cpu list, expected something like 0-3,6");
+    let parse_cpu = |text: &str| text.trim().parse::<usize>().ok().filter(|cpu| *cpu <= MAX_CPU).ok_or_else(invalid);
+    let mut cpus = vec![];
+
+    for part in input.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+      match part.split_once('-') {
+        Some((first, last)) => cpus.extend(parse_cpu(first)?..=parse_cpu(last)?),
+        None => cpus.push(parse_cpu(part)?),
+      }
+    }
+
+    if cpus.is_empty() {
+      return Err(invalid());
+    }
+
+    Ok(Self(cpus))
+  }
+}
+
+impl From<CpuList> for String {
+  fn from(cpus: CpuList) -> Self {
+    cpus.0.iter().map(usize::to_string).collect::<Vec<String>>().join(",")
+  }
+}
+
+/// A file mode creation mask in octal, e.g. `"027"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Umask(pub libc::mode_t);
+
+impl TryFrom<String> for Umask {
+  type Error = String;
+
+  fn try_from(text: String) -> Result<Self, Self::Error> {
+    libc::mode_t::from_str_radix(text.trim(), 8)
+      .ok()
+      .filter(|mask| *mask <= 0o777)
+      .map(Self)
+      .ok_or_else(|| format!("`{text}` is not a valid umask, expected an octal mode like 022"))
+  }
+}
+
+impl From<Umask> for String {
+  fn from(umask: Umask) -> Self {
+    format!("{:03o}", umask.0)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IoPriorityClass {
+  Realtime,
+  BestEffort,
+  Idle,
+}
+
+impl IoPriorityClass {
+  const fn id(self) -> u32 {
+    match self {
+      Self::Realtime => 1,
+      Self::BestEffort => 2,
+      Self::Idle => 3,
+    }
+  }
+}
+
+struct_gen! {
+  /// Limits and scheduling settings that are applied in the child right before `exec`.
+  pub struct ProcessLimits use Clone {
+    pub let nofile: Option<LimitValue> = None;
+    pub let nproc: Option<LimitValue> = None;
+    pub let address_space: Option<LimitValue> = None;
+    pub let cpu: Option<LimitValue> = None;
+    pub let core: Option<LimitValue> = None;
+    /// Whether the hard limits are set as well. Otherwise only the soft limits are, at most up to the current hard limits.
+    pub let hard: bool = false;
+    pub let nice: Option<i32> = None;
+    pub let ioprio: Option<(IoPriorityClass, u32)> = None;
+    pub let cpu_affinity: Option<CpuList> = None;
+    pub let umask: Option<Umask> = None;
+  }
+
+  mod implementation {
+    pub fn is_empty(self: &Self) -> bool {
+      self.nofile.is_none() && self.nproc.is_none() && self.address_space.is_none() && self.cpu.is_none() && self.core.is_none()
+        && self.nice.is_none() && self.ioprio.is_none() && self.cpu_affinity.is_none() && self.umask.is_none()
+    }
+
+    /// The resource limits by their name in `[limits]`.
+    fn resources(self: &Self) -> [(&'static str, Resource, Option<LimitValue>); 5] {
+      [
+        ("nofile", libc::RLIMIT_NOFILE, self.nofile),
+        ("nproc", libc::RLIMIT_NPROC, self.nproc),
+        ("as", libc::RLIMIT_AS, self.address_space),
+        ("cpu", libc::RLIMIT_CPU, self.cpu),
+        ("core", libc::RLIMIT_CORE, self.core),
+      ]
+    }
+
+    /// The limits above the hard limits ctr runs with, along with those hard limits.
+    /// Unless `hard` is set, the command gets the hard limit instead.
+    pub fn above_hard_limits(self: &Self) -> Vec<(&'static str, LimitValue, LimitValue)> {
+      if self.hard {
+        return vec![];
+      }
+
+      self.resources().into_iter().filter_map(|(name, resource, limit)| {
+        let LimitValue(value) = limit?;
+        let mut current = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+
+        // SAFETY: `current` is valid for writes
+        let max = (unsafe { libc::getrlimit(resource, &mut current) } == 0_i32).then_some(current.rlim_max)?;
+        (value > max).then_some((name, LimitValue(value), LimitValue(max)))
+      }).collect()
+    }
+
+    /// Applies the settings to the calling process.
+    /// Runs between `fork` and `exec`, so it must not allocate.
+    pub fn apply(self: &Self) -> std::io::Result<()> {
+      macro_rules! check {
+        ($result:expr) => {
+          if ($result).is_negative() {
+            return Err(std::io::Error::last_os_error());
+          }
+        };
+      }
+
+      // SAFETY: only async-signal-safe syscalls on plain values of the current process
+      unsafe {
+        for (_, resource, limit) in self.resources() {
+          let Some(LimitValue(value)) = limit else {
+            continue;
+          };
+
+          // raising a hard limit needs CAP_SYS_RESOURCE, and lowering it cannot be undone
+          let mut current = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+          check!(libc::getrlimit(resource, &mut current));
+          let wanted = if self.hard {
+            libc::rlimit { rlim_cur: value, rlim_max: value }
+          } else {
+            libc::rlimit { rlim_cur: value.min(current.rlim_max), rlim_max: current.rlim_max }
+          };
+
+          check!(libc::setrlimit(resource, &wanted));
+        }
+
+        if let Some(nice) = self.nice {
+          check!(libc::setpriority(libc::PRIO_PROCESS, 0, nice));
+        }
+
+        if let Some((class, level)) = self.ioprio {
+          let priority = (class.id() << IOPRIO_CLASS_SHIFT) | level;
+          check!(libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, priority));
+        }
+
+        if let Some(CpuList(ref cpus)) = self.cpu_affinity {
+          let mut set = std::mem::zeroed::<libc::cpu_set_t>();
+          for cpu in cpus {
+            libc::CPU_SET(*cpu, &mut set);
+          }
+
+          check!(libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set));
+        }
+
+        if let Some(Umask(mask)) = self.umask {
+          libc::umask(mask);
+        }
+      }
+
+      Ok(())
+    }
+  }
+}
@@ -0,0 +1,179 @@
+use std::{
+  fs::{self, File, OpenOptions},
+  io::{ErrorKind, Read, Write},
+  path::{Path, PathBuf},
+  sync::{Arc, Mutex},
+  thread::JoinHandle,
+};
+
+use std_v2::{console::CONSOLE, struct_gen};
+
+struct_gen! {
+  /// Where and how the output of a run is written, see the `[log]` section of presets and `--log`.
+  pub struct OutputLog use Clone {
+    pub let path: PathBuf = PathBuf::new();
+    pub let append: bool = true;
+    /// Size in bytes after which the file is rotated.
+    pub let max_size: Option<u64> = None;
+    /// Number of rotated files to keep, as `<path>.1` (newest) to `<path>.<keep>`.
+    pub let keep: u32 = 5;
+    pub let timestamps: bool = false;
+    pub let prefix: bool = false;
+  }
+
+  mod implementation {
+    fn rotated_path(self: &Self, index: u32) -> PathBuf {
+      let mut path = self.path.clone().into_os_string();
+      path.push(format!(".{index}"));
+      PathBuf::from(path)
+    }
+
+    /// Shifts `<path>.N` to `<path>.N+1`, dropping the oldest file, and moves the current file to `<path>.1`.
+    fn rotate(self: &Self) -> std::io::Result<()> {
+      if self.keep == 0 {
+        return fs::remove_file(&self.path);
+      }
+
+      for index in (1..self.keep).rev() {
+        let from = self.rotated_path(index);
+        if from.exists() {
+          fs::rename(from, self.rotated_path(index.saturating_add(1)))?;
+        }
+      }
+
+      fs::rename(&self.path, self.rotated_path(1))
+    }
+  }
+}
+
+/// The open log file of a run, shared by the threads copying stdout and stderr.
+pub struct LogWriter {
+  log: OutputLog,
+  file: File,
+  size: u64,
+}
+
+impl LogWriter {
+  pub fn open(log: &OutputLog) -> std::io::Result<Self> {
+    if let Some(parent) = log.path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+      fs::create_dir_all(parent)?;
+    }
+
+    let file = Self::open_file(&log.path, log.append)?;
+    let size = file.metadata()?.len();
+
+    Ok(Self { log: log.clone(), file, size })
+  }
+
+  fn open_file(path: &Path, append: bool) -> std::io::Result<File> {
+    OpenOptions::new().create(true).write(true).append(append).truncate(!append).open(path)
+  }
+
+  /// Writes one line of output, rotating the file first if it would grow past `max_size`.
+  pub fn write_line(&mut self, stream: &str, line: &[u8]) -> std::io::Result<()> {
+    let mut entry = vec![];
+    if self.log.timestamps {
+      entry.extend(format!("{} ", timestamp()).into_bytes());
+    }
+
+    if self.log.prefix {
+      entry.extend(format!("[{stream}] ").into_bytes());
+    }
+
+    entry.extend_from_slice(line);
+    if !entry.ends_with(b"\n") {
+      entry.push(b'\n');
+    }
+
+    let entry_size = u64::try_from(entry.len()).unwrap_or(u64::MAX);
+    if self.log.max_size.is_some_and(|max_size| self.size > 0 && self.size.saturating_add(entry_size) > max_size) {
+      self.log.rotate()?;
+      self.file = Self::open_file(&self.log.path, false)?;
+      self.size = 0;
+    }
+
+    self.file.write_all(&entry)?;
+    self.size = self.size.saturating_add(entry_size);
+
+    Ok(())
+  }
+}
+
+/// Copies `source` to `terminal` (if any) and line by line to the log.
+/// Runs on its own thread until the child closes the stream.
+pub fn tee<R, W>(mut source: R, mut terminal: Option<W>, stream: &'static str, writer: Arc<Mutex<LogWriter>>) -> JoinHandle<()>
+where
+  R: Read + Send + 'static,
+  W: Write + Send + 'static,
+{
+  std::thread::spawn(move || {
+    let mut buffer = [0_u8; 4096];
+    let mut line = vec![];
+    let mut warned = false;
+    let mut write_line = |entry: &[u8]| {
+      let result = match writer.lock() {
+        Ok(mut log) => log.write_line(stream, entry),
+        Err(_) => Ok(()),
+      };
+
+      // keep the command running, but only complain once
+      if let Err(err) = result {
+        if !warned {
+          CONSOLE.warn(format!("Failed to write to the log: {err}"));
+          warned = true;
+        }
+      }
+    };
+
+    // written to the terminal as it arrives rather than by line, so that prompts and progress bars show up right away
+    loop {
+      let read = match source.read(&mut buffer) {
+        Ok(read) => read,
+        Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+        Err(_) => break,
+      };
+
+      let Some(chunk) = buffer.get(..read).filter(|chunk| !chunk.is_empty()) else {
+        break;
+      };
+
+      if let Some(ref mut output) = terminal {
+        let _ = output.write_all(chunk).and_then(|()| output.flush());
+      }
+
+      line.extend_from_slice(chunk);
+      while let Some(end) = line.iter().position(|byte| *byte == b'\n') {
+        write_line(&line.drain(..=end).collect::<Vec<u8>>());
+      }
+    }
+
+    if !line.is_empty() {
+      write_line(&line);
+    }
+  })
+}
+
+/// The local time as `YYYY-MM-DD HH:MM:SS`.
+fn timestamp() -> String {
+  // SAFETY: a null pointer makes `time` only return the value
+  local_time(unsafe { libc::time(std::ptr::null_mut()) })
+}
+
+/// `seconds` since the unix epoch as a local `YYYY-MM-DD HH:MM:SS`.
+pub fn local_time(seconds: libc::time_t) -> String {
+  // SAFETY: `tm` is plain data and is fully initialized by `localtime_r`
+  let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+  if unsafe { libc::localtime_r(&seconds, &mut tm) }.is_null() {
+    return seconds.to_string();
+  }
+
+  format!(
+    "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+    tm.tm_year.saturating_add(1900),
+    tm.tm_mon.saturating_add(1),
+    tm.tm_mday,
+    tm.tm_hour,
+    tm.tm_min,
+    tm.tm_sec
+  )
+}
@@ -1,15 +1,119 @@
-use std::{os::unix::process::CommandExt, path::Path, process::Command};
+use std::{
+  collections::BTreeMap,
+  fs::OpenOptions,
+  io::{Read, Write},
+  os::unix::process::CommandExt,
+  path::{Path, PathBuf},
+  process::{Child, Command, ExitStatus, Stdio},
+  sync::{Arc, LazyLock, Mutex},
+  thread::sleep,
+  time::{Duration, Instant},
+};
 
 use clap::Args;
 use std_v2::{
-  command::Operation,
+  command::{types::ArgumentType, Operation},
   console::CONSOLE,
   env::consts::{BINARY_NAME, USER_CONFIG_DIR},
-  struct_gen,
+  lazy_var, struct_gen,
 };
-mod ser;
+mod dotenv;
+mod deps;
+pub mod duration;
+pub mod elevate;
+mod group;
+pub mod history;
+mod identity;
+mod interpolate;
+pub mod job;
+pub mod limits;
+pub mod log;
+mod params;
+mod pty;
+pub mod sandbox;
+pub mod ser;
+pub mod signals;
+mod watch;
+use deps::{Dependencies, SKIP_DEPENDENCIES_VAR};
+use duration::HumanDuration;
+use group::Group;
+use history::HistoryEntry;
+use identity::Identity;
+use job::{process_start_time, Job, JOB_ID_VAR, RESTART_ID_VAR};
+use log::LogWriter;
+use params::PresetArguments;
+use pty::Pty;
 use ser::*;
+use signals::Signal;
 use uzers::{get_group_by_name, get_user_by_name};
+use watch::Watcher;
+
+use super::{check_conflicts, escape_markup, notify::Options as NotifyCommand, preset::list::Options as ListCommand, print_data, ps::Options as PsCommand};
+
+lazy_var!(pub PRESETS_DIR<PathBuf> {
+  USER_CONFIG_DIR.join("presets")
+});
+
+/// Exit code used when the command was stopped by `--timeout`, like coreutils `timeout`.
+const TIMEOUT_EXIT_CODE: i32 = 124;
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Which presets directories are searched, see `--local` and `--global`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresetScope {
+  Any,
+  Local,
+  Global,
+}
+
+/// The `.ctr/presets` directories in the current directory and its parents, nearest first.
+/// The search stops at the root of the git repository, if there is one.
+pub fn local_presets_dirs() -> Vec<PathBuf> {
+  let Ok(cwd) = std::env::current_dir() else {
+    return vec![];
+  };
+
+  let mut dirs = vec![];
+  for dir in cwd.ancestors() {
+    let presets_dir = dir.join(".ctr").join("presets");
+    if presets_dir.is_dir() && presets_dir != *PRESETS_DIR {
+      dirs.push(presets_dir);
+    }
+
+    if dir.join(".git").exists() {
+      break;
+    }
+  }
+
+  dirs
+}
+
+pub fn global_preset_path(name: &str) -> PathBuf {
+  PRESETS_DIR.join(format!("{name}.toml"))
+}
+
+/// Finds the preset `name` in `scope`, preferring project-local presets over global ones.
+pub fn locate_preset(name: &str, scope: PresetScope) -> Option<PathBuf> {
+  let local = (scope != PresetScope::Global).then(|| {
+    local_presets_dirs().into_iter().map(|dir| dir.join(format!("{name}.toml"))).find(|path| path.is_file())
+  }).flatten();
+
+  local.or_else(|| (scope != PresetScope::Local).then(|| global_preset_path(name)).filter(|path| path.is_file()))
+}
+
+/// The preset `name` extended by the preset at `child`.
+/// Presets next to `child` are preferred, so that local presets can extend each other.
+pub fn parent_preset_path(child: &Path, name: &str) -> PathBuf {
+  child.parent()
+    .map(|dir| dir.join(format!("{name}.toml")))
+    .filter(|path| path.is_file() && path != child)
+    .unwrap_or_else(|| preset_path(name))
+}
+
+/// The preset `name`, or where it would be stored globally if it does not exist.
+pub fn preset_path(name: &str) -> PathBuf {
+  locate_preset(name, PresetScope::Any).unwrap_or_else(|| global_preset_path(name))
+}
 
 struct_gen! {
   #[usage(Flags, Operand { name: "binary".to_string()}, Variadic { name: "args".to_string()})]
@@ -26,7 +130,49 @@ struct_gen! {
     #[arg(short = 'I', long), flag("Ignore the config file")]
     let ignore_config: bool = false;
 
-    #[arg(trailing_var_arg = true, allow_hyphen_values = true), variadic(name = "args", about = "Arguments passed to the binary")]
+    #[arg(short = 'N', long), flag("Execute the arguments directly instead of through the shell")]
+    let no_shell: bool = false;
+
+    #[arg(long), longflag("Only look for presets in .ctr/presets of the current project")]
+    let local: bool = false;
+
+    #[arg(long), longflag("Only look for presets in the global presets directory")]
+    let global: bool = false;
+
+    #[arg(long), longflag("List the available presets")]
+    let list: bool = false;
+
+    #[arg(long), longflag("Run all given presets in parallel")]
+    let parallel: bool = false;
+
+    #[arg(long), longflag("Stop all members of a group as soon as one of them fails")]
+    let fail_fast: bool = false;
+
+    #[arg(short, long), flag("Set a preset parameter", example = "name=value")]
+    let param: Vec<String> = Vec::new();
+
+    #[arg(short, long), flag("Stop the command after the given duration", example = "5m")]
+    let timeout: Option<HumanDuration> = None;
+
+    #[arg(short, long), flag("Also write the command output to a file", example = "run.log")]
+    let log: Option<PathBuf> = None;
+
+    #[arg(short, long), flag("Run the command again when files below the given path change", example = "src/**/*.rs")]
+    let watch: Vec<String> = Vec::new();
+
+    #[arg(long), longflag("Show a desktop notification when the command finishes")]
+    let notify: bool = false;
+
+    #[arg(long), longflag("Run the command under a pseudo-terminal, so it keeps colors when its output is logged")]
+    let pty: bool = false;
+
+    #[arg(long), longflag("Print what would be executed without running anything")]
+    let dry_run: bool = false;
+
+    #[arg(long), longflag("Like --dry-run, and also show where each setting comes from")]
+    let explain: bool = false;
+
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true), variadic(name = "args", about = "Arguments passed to the binary. A single argument is run as a shell script as it is, use -N to run a path with spaces")]
     let &mut args: Vec<String> = Vec::new();
   }
 
@@ -36,19 +182,102 @@ struct_gen! {
     // CONSOLE.print("<brightblue>binary</brightblue> can also be the name of the config file in <bold>~/.config/ctr/presets</bold>\n");
 
     fn main(self: &Self) -> std::io::Result<()> {
-      (self.help).then(|| Self::usage(0));
+      // `ctr run <preset> --help` lists the parameters of the preset instead
+      (self.help && self.args.is_empty()).then(|| Self::usage(0));
+      check_conflicts(vec![("local", self.local), ("global", self.global)]);
+
+      if self.list {
+        ListCommand::print_presets(self.scope());
+        return Ok(());
+      }
 
       if self.args.is_empty() {
         CONSOLE.exit(format!("No binary specified. Use <magenta>{BINARY_NAME} run --help</magenta> for additional information"));
       }
 
+      if self.parallel {
+        let group = Group { name: "parallel".to_owned(), members: self.args.clone(), fail_fast: self.fail_fast, flags: self.scope_flags() };
+        std::process::exit(self.run_group(&group));
+      }
+
+      let mut config = self.get_configs();
+      let order = match self.preset_name() {
+        Some(ref preset) if std::env::var_os(SKIP_DEPENDENCIES_VAR).is_none() => Dependencies::resolve(preset, &config, self.scope()),
+        _ => vec![],
+      };
+
+      let members = config.group.as_ref().and_then(|settings| settings.members.clone()).map(|members| Group {
+        name: self.args[0].clone(),
+        members,
+        fail_fast: self.fail_fast || config.group.as_ref().and_then(|settings| settings.fail_fast).unwrap_or(false),
+        flags: self.scope_flags(),
+      });
+
+      if members.is_some() && config.general.tty == Some(true) {
+        CONSOLE.warn("<brightblue>general.tty</brightblue> is not applied to groups, set it in the member presets instead");
+      }
+
+      if (self.dry_run || self.explain) && !order.is_empty() {
+        let names = order.iter().map(|(name, _)| name.as_str()).collect::<Vec<&str>>();
+        print_data(&[("depends_on".to_owned(), escape_markup(&names.join(" -> ")))]);
+      }
+
+      let overrides = self.apply_overrides(&mut config);
+      (self.explain && members.is_none()).then(|| self.print_sources(&config, &overrides));
+
+      let options = LaunchOptions::from(config);
+
+      // dependencies would be stopped when ctr exits after detaching, and their output threads must not exist when `detach` forks
+      if options.daemonize && members.is_none() && !order.is_empty() {
+        CONSOLE.exit("<brightblue>depends_on</brightblue> cannot be combined with <brightblue>general.deamonize</brightblue> or --daemonize, start the dependencies as jobs with <magenta>ctr run -D</magenta> instead");
+      }
+
+      if self.dry_run || self.explain {
+        match members {
+          Some(ref group) => self.print_group(group),
+          None => self.print_dry_run(&options),
+        }
+
+        return Ok(());
+      }
 
-      let config = self.get_configs();
-      self.launch(&LaunchOptions::from(config))
+      let started = history::now_millis();
+      let mut dependencies = Dependencies::start(order, &self.scope_flags());
+      let code = match members {
+        Some(ref group) => self.run_group(group),
+        None => self.launch(&options),
+      };
+
+      dependencies.stop();
+      self.record_history(&options, started, Some(code));
+      std::process::exit(code)
     }
   }
 
   mod utils {
+    /// Joins the trailing arguments into a script for `$SHELL -c`.
+    /// A single argument is taken as a script as-is, multiple arguments are quoted individually.
+    fn shell_script(self: &Self) -> String {
+      match self.args.as_slice() {
+        [script] => script.to_owned(),
+        args => args.iter().map(|arg| Self::quote_arg(arg)).collect::<Vec<String>>().join(" "),
+      }
+    }
+
+    fn quote_arg(arg: &str) -> String {
+      if !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || "_-+=@%:,./".contains(c)) {
+        return arg.to_owned();
+      }
+
+      format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+
+    /// The shell a command runs through with `run_as.login_shell`: the login shell of the user, or the preset shell.
+    fn login_shell(options: &LaunchOptions, identity: &Identity) -> String {
+      identity.shell().as_ref().filter(|shell| shell.is_file())
+        .map_or_else(|| options.shell.to_owned(), |shell| shell.display().to_string())
+    }
+
     fn get_group_id(self: &Self, group: &Option<String>) -> Option<u32> {
       if let Some(group) = group {
         if let Ok(gid) = group.parse::<u32>() {
@@ -78,104 +307,892 @@ struct_gen! {
     }
   }
 
-  mod implementation {
-    pub fn get_configs(self: &Self) -> LaunchConfig {
-      let config_path = &*USER_CONFIG_DIR.join(format!("presets/{}.toml", self.args()[0]));
-      let mut default_config = LaunchConfig::default();
+  mod group {
+    /// The flags that select where presets are looked up, passed on to the members of a group.
+    fn scope_flags(self: &Self) -> Vec<String> {
+      match self.scope() {
+        PresetScope::Any => vec![],
+        PresetScope::Local => vec!["--local".to_owned()],
+        PresetScope::Global => vec!["--global".to_owned()],
+      }
+    }
 
-      if self.ignore_config || !config_path.exists() {
-        if !self.args.is_empty() && default_config.general.command.is_none() {
-          default_config.general.command = Some(self.args.join(" "));
+    fn print_group(self: &Self, group: &Group) {
+      print_data(&[
+        ("group".to_owned(), escape_markup(&group.name)),
+        ("members".to_owned(), escape_markup(&group.members.join(", "))),
+        ("fail_fast".to_owned(), group.fail_fast.to_string()),
+      ]);
+    }
+
+    /// Runs the members of `group` and returns the exit code of the group.
+    fn run_group(self: &Self, group: &Group) -> i32 {
+      if group.members.is_empty() {
+        CONSOLE.exit(format!("The group <brightmagenta>{}</brightmagenta> has no members", group.name));
+      }
+
+      if self.dry_run || self.explain {
+        self.print_group(group);
+        return 0_i32;
+      }
+
+      if self.daemonize || self.timeout.is_some() || self.log.is_some() || self.pty {
+        CONSOLE.warn("--daemonize, --timeout, --log and --pty are not applied to groups, set them in the member presets instead");
+      }
+
+      group.run()
+    }
+  }
+
+  mod dry_run {
+    /// Applies the flags that override preset settings, and returns the fields they set.
+    fn apply_overrides(self: &Self, config: &mut LaunchConfig) -> Vec<&'static str> {
+      let mut overrides = vec![];
+
+      if self.daemonize {
+        config.general.deamonize = Some(true);
+        overrides.push("general.deamonize");
+      }
+
+      if let Some(timeout) = self.timeout {
+        config.general.timeout = Some(timeout);
+        overrides.push("general.timeout");
+      }
+
+      if let Some(ref path) = self.log {
+        config.log.get_or_insert_with(LaunchConfigLog::default).path = Some(path.display().to_string());
+        overrides.push("log.path");
+      }
+
+      if !self.watch.is_empty() {
+        config.watch.get_or_insert_with(LaunchConfigWatch::default).paths = Some(self.watch.clone());
+        overrides.push("watch.paths");
+      }
+
+      if self.pty {
+        config.general.tty = Some(true);
+        overrides.push("general.tty");
+      }
+
+      if self.notify && config.notify.is_none() {
+        config.notify = Some(LaunchConfigNotify { on: Some(NotifyOn::Always), min_duration: None });
+        overrides.push("notify.on");
+      }
+
+      overrides
+    }
+
+    /// Prints every resolved field along with the default, preset file or flag that set it.
+    fn print_sources(self: &Self, config: &LaunchConfig, overrides: &[&str]) {
+      let preset = self.preset_file();
+      let sources = preset.as_ref().map(|path| LaunchConfig::sources(path)).unwrap_or_else(|| {
+        LaunchConfig::flatten(&std_v2::toml::Value::try_from(LaunchConfig::default()).unwrap_or(std_v2::toml::Value::Boolean(false)))
+          .into_iter()
+          .map(|(key, _)| (key, "default".to_owned()))
+          .collect()
+      });
+
+      let has_arguments = self.args.len() > 1 || !self.param.is_empty();
+      let Ok(resolved) = std_v2::toml::Value::try_from(config) else {
+        CONSOLE.exit("Failed to serialize the resolved configuration");
+      };
+
+      let data = LaunchConfig::flatten(&resolved).into_iter()
+        .map(|(key, value)| {
+          let file = sources.get(&key).cloned().unwrap_or("default".to_owned());
+          let source = match key.as_str() {
+            field if overrides.contains(&field) => "command line".to_owned(),
+            "general.command" if preset.is_none() => "command line".to_owned(),
+            "general.command" if has_arguments => format!("{file} with arguments"),
+            _ => file,
+          };
+
+          (key, format!("{} <brightblack>({})</brightblack>", escape_markup(&value), escape_markup(&source)))
+        })
+        .collect::<Vec<(String, String)>>();
+
+      print_data(&data);
+      CONSOLE.print("");
+    }
+
+    /// Prints what `launch` would execute, without running anything.
+    fn print_dry_run(self: &Self, options: &LaunchOptions) {
+      let args = self.command_args(options, &options.command);
+      let quote = |argv: &[String]| escape_markup(&argv.iter().map(|arg| Self::quote_arg(arg)).collect::<Vec<String>>().join(" "));
+
+      let cwd = options.current_dir.clone().unwrap_or_else(|| std::env::current_dir().unwrap_or_default().display().to_string());
+      let (user, group) = options.run_as.as_ref().map_or((None, None), |run_as| (run_as.user.clone(), run_as.group.clone()));
+
+      // SAFETY: `getuid` and `getgid` cannot fail
+      let (current_uid, current_gid) = unsafe { (libc::getuid(), libc::getgid()) };
+      let identity = match options.identity {
+        _ if options.elevate.is_some() => {
+          let uid = self.get_user_id(&user).unwrap_or(0);
+          let gid = self.get_group_id(&group).map_or("default".to_owned(), |gid| gid.to_string());
+          format!("uid {uid}, gid {gid} <brightblack>(through {})</brightblack>", options.elevate.unwrap_or_default())
+        },
+        Some(ref target) => {
+          let groups = target.groups().iter().map(u32::to_string).collect::<Vec<String>>().join(", ");
+          let login = if *target.login_shell() { ", login shell" } else { "" };
+          format!("uid {} ({}), gid {}, groups {groups}{login}", target.uid(), escape_markup(target.name()), target.gid())
+        },
+        None => format!("uid {current_uid}, gid {current_gid}"),
+      };
+
+      let mut data = vec![
+        ("argv".to_owned(), quote(&args)),
+        ("cwd".to_owned(), escape_markup(&cwd)),
+        ("identity".to_owned(), identity),
+        ("daemonize".to_owned(), if options.daemonize { "yes" } else { "no" }.to_owned()),
+        ("tty".to_owned(), if options.tty { "yes" } else { "no" }.to_owned()),
+      ];
+
+      if let Some(timeout) = options.timeout {
+        data.push(("timeout".to_owned(), HumanDuration(timeout).to_string()));
+      }
+
+      data.push(("stop".to_owned(), format!("{}, SIGKILL after {}", options.stop_signal, HumanDuration(options.stop_grace))));
+
+      if options.restart != RestartPolicy::No {
+        let max = options.max_restarts.map_or("unlimited".to_owned(), |max| max.to_string());
+        data.push(("restart".to_owned(), format!("{:?}, at most {max} times", options.restart).to_lowercase()));
+      }
+
+      if let Some(ref sandbox) = options.sandbox {
+        data.push(("sandbox".to_owned(), escape_markup(&sandbox.describe())));
+      }
+
+      if let Some(ref log) = options.log {
+        data.push(("log".to_owned(), escape_markup(&log.path.display().to_string())));
+      }
+
+      if let Some(ref watch) = options.watch {
+        let on_change = format!("{:?}", watch.on_change.unwrap_or_default()).to_lowercase();
+        data.push(("watch".to_owned(), format!("{} <brightblack>({on_change} on change)</brightblack>", escape_markup(&watch.paths.clone().unwrap_or_default().join(", ")))));
+      }
+
+      if let Some(ref notify) = options.notify {
+        let min_duration = notify.min_duration.map_or(String::new(), |min| format!(", if it ran for at least {min}"));
+        data.push(("notify".to_owned(), format!("{:?}{min_duration}", notify.on.unwrap_or_default()).to_lowercase()));
+      }
+
+      let hooks = &options.hooks;
+      for (stage, stage_hooks) in [("before", &hooks.before), ("on_success", &hooks.on_success), ("on_failure", &hooks.on_failure), ("after", &hooks.after)] {
+        for hook in stage_hooks.iter().flatten() {
+          data.push((format!("hook {stage}"), quote(&self.command_args(options, hook.command()))));
         }
+      }
 
-        return default_config;
+      print_data(&data);
+
+      let current = std::env::vars_os()
+        .map(|(key, value)| (key.to_string_lossy().to_string(), value.to_string_lossy().to_string()))
+        .collect::<BTreeMap<String, String>>();
+      let mut prepared = Command::new(args.first().map_or("", String::as_str));
+      Self::apply_environment(options, &mut prepared);
+
+      let mut environment = if options.preserve_env { current.clone() } else { BTreeMap::new() };
+      for (key, value) in prepared.get_envs() {
+        let name = key.to_string_lossy().to_string();
+        match value {
+          Some(set) => environment.insert(name, set.to_string_lossy().to_string()),
+          None => environment.remove(&name),
+        };
       }
 
-      let config: LaunchConfig = std_v2::toml::parse_file(config_path).unwrap_or_else(|err| CONSOLE.exit(format!("{err}")));
+      let mut diff = vec![];
+      for (key, value) in &environment {
+        match current.get(key) {
+          None => diff.push(format!("<green>+ {key}={}</green>", escape_markup(value))),
+          Some(old) if old != value => diff.push(format!("<yellow>~ {key}={}</yellow>", escape_markup(value))),
+          Some(_) => {},
+        }
+      }
 
-      default_config.merge(config);
-      default_config.general.working_dir = default_config.general.working_dir.map(|e| {
-        match Path::new(e.as_str()).canonicalize() {
-          Ok(path) => path.to_string_lossy().to_string(),
-          _ => e
+      if options.preserve_env {
+        for key in current.keys().filter(|key| !environment.contains_key(*key)) {
+          diff.push(format!("<red>- {key}</red>"));
         }
-      });
+      } else {
+        diff.push("<red>- all other variables <brightblack>(preserve_env = false)</brightblack></red>".to_owned());
+      }
 
-      default_config
+      if !diff.is_empty() {
+        CONSOLE.print("\n<brightblue>environment</brightblue>");
+        for line in diff {
+          CONSOLE.print(line);
+        }
+      }
     }
+  }
 
-    pub fn launch(self: &Self, options: &LaunchOptions) -> std::io::Result<()>  {
-      let args = {
-        let mut args = vec![options.shell.to_owned(), "-c".to_owned()];
+  mod daemon {
+    /// Detaches the command with a double fork and records it as a job.
+    /// The intermediate process starts a new session and writes the job record,
+    /// the grandchild execs the command with stdin from /dev/null and its output in the job log.
+    fn detach(self: &Self, options: &LaunchOptions, args: &[String]) -> ! {
+      if options.restart != RestartPolicy::No || options.timeout.is_some() || options.watch.is_some() || options.tty {
+        CONSOLE.warn("Restart policies, timeouts, watch and tty are not applied to daemonized commands");
+      }
 
-        if let Some(ref run_as) = options.run_as {
-          if run_as.sudo.unwrap_or(false) {
-            let sudo_cmd = {
-              let mut sudo = vec!["sudo".to_owned()];
+      if options.log.is_some() {
+        CONSOLE.warn("Daemonized commands write their output to the job log, use <magenta>ctr logs</magenta> to read it");
+      }
 
-              if let Some(user) = run_as.user.as_ref() {
-                sudo.extend(vec!["-u".to_owned(), user.to_owned()]);
-              }
+      // `ctr restart` passes the id along so the job keeps it
+      let name = std::env::var(RESTART_ID_VAR).ok().or_else(|| self.preset_name()).unwrap_or_else(|| {
+        Path::new(&self.args[0]).file_name().map_or("job".to_owned(), |name| name.to_string_lossy().to_string())
+      });
+
+      if let Err(err) = std::fs::create_dir_all(&*job::JOBS_DIR) {
+        CONSOLE.exit(format!("Failed to create {}: {err}", job::JOBS_DIR.display()));
+      }
+
+      let id = Job::unique_id(&name);
+      let log_path = Job::log_path(&id);
+      let log = OpenOptions::new().create(true).append(true).open(&log_path)
+        .unwrap_or_else(|err| CONSOLE.exit(format!("Failed to open {}: {err}", log_path.display())));
+      let log_err = log.try_clone().unwrap_or_else(|err| CONSOLE.exit(format!("Failed to open {}: {err}", log_path.display())));
+
+      let mut command = self.prepare_command(options, args);
+      command.stdin(Stdio::null()).stdout(log).stderr(log_err).process_group(0).env(JOB_ID_VAR, &id);
+
+      let mut job = Job {
+        id: id.clone(),
+        preset: self.preset_name(),
+        started: job::now(),
+        command: options.command.to_string(),
+        invoked_from: std::env::current_dir().unwrap_or_default(),
+        argv: std::env::args_os().map(|arg| arg.to_string_lossy().to_string()).collect(),
+        stop_signal: Some(options.stop_signal),
+        stop_grace: Some(HumanDuration(options.stop_grace)),
+        ..Job::default()
+      };
+
+      // errors from the forked processes are reported back through the pipe,
+      // which is closed without any data once the command has been exec'd
+      let (mut reader, mut writer) = std::io::pipe().unwrap_or_else(|err| CONSOLE.exit(format!("Failed to daemonize: {err}")));
+
+      // SAFETY: no other threads exist yet, dependencies are refused for daemonized commands in `main`, and both children only exit through `_exit` or `exec`
+      match unsafe { libc::fork() } {
+        -1 => CONSOLE.exit(format!("Failed to daemonize: {}", std::io::Error::last_os_error())),
+        0 => {
+          drop(reader);
+
+          unsafe {
+            libc::setsid();
+          }
+
+          let code = match unsafe { libc::fork() } {
+            -1 => {
+              let _ = writeln!(writer, "{}", std::io::Error::last_os_error());
+              1_i32
+            },
+            0 => {
+              let err = command.exec();
+              let _ = writeln!(writer, "{err}");
+              127_i32
+            },
+            pid => {
+              // the start time is looked up by the parent, sysinfo is not safe to use after a fork
+              job.pid = u32::try_from(pid).unwrap_or_default();
 
-              if let Some(group) = run_as.group.as_ref() {
-                sudo.extend(vec!["-g".to_owned(), group.to_owned()]);
+              if let Err(err) = job.save() {
+                let _ = writeln!(writer, "Failed to save the job: {err}");
               }
 
-              sudo.push("--".to_owned());
+              0_i32
+            },
+          };
 
-              sudo
-            };
+          drop(writer);
+          unsafe { libc::_exit(code) }
+        },
+        intermediate => {
+          drop(writer);
 
-            for (i, arg) in sudo_cmd.iter().enumerate() {
-              args.insert(i, arg.to_owned());
+          unsafe {
+            libc::waitpid(intermediate, std::ptr::null_mut(), 0);
+          }
+
+          let mut error = String::new();
+          let _ = reader.read_to_string(&mut error);
+
+          if !error.trim().is_empty() {
+            if let Some(record) = Job::load(&id) {
+              record.remove();
             }
+
+            CONSOLE.exit(format!("Failed to run `{}`: {}", args.join(" "), error.trim()));
           }
-        }
 
-        args.push(
-          if options.daemonize {
-            format!("setsid {}", options.command)
-          } else {
-            options.command.to_owned()
+          let mut record = Job::load(&id).unwrap_or_else(|| CONSOLE.exit(format!("Failed to read the record of job <brightmagenta>{id}</brightmagenta>")));
+          record.process_start = process_start_time(record.pid);
+          if let Err(err) = record.save() {
+            CONSOLE.warn(format!("Failed to save the job: {err}"));
           }
-        );
-        args
-      };
 
-      if let Some(binary) = args.first() {
-        let mut command = Command::new(binary);
+          self.record_history(options, history::now_millis(), None);
+
+          let pid = record.pid;
+          CONSOLE.print(format!("Started job <brightmagenta>{id}</brightmagenta> <brightblack>(pid {pid}, log {})</brightblack>", log_path.display()));
+          std::process::exit(0);
+        },
+      }
+    }
+  }
+
+  mod watch {
+    /// Runs the command, and again whenever a watched file changes, until ctr is interrupted.
+    fn run_watched(self: &Self, options: &LaunchOptions, args: &[String], watch: &LaunchConfigWatch) -> i32 {
+      if options.restart != RestartPolicy::No || options.timeout.is_some() {
+        CONSOLE.warn("Restart policies and timeouts are not applied in watch mode");
+      }
+
+      if options.tty {
+        CONSOLE.warn("Commands are not run under a pseudo-terminal in watch mode, since Ctrl-C has to reach ctr");
+      }
+
+      let paths = watch.paths.clone().unwrap_or_default();
+      if paths.is_empty() {
+        CONSOLE.exit("There is nothing to watch, set <brightblue>watch.paths</brightblue> or use <magenta>--watch</magenta>");
+      }
+
+      let base = options.current_dir.as_ref().map_or_else(|| std::env::current_dir().unwrap_or_default(), PathBuf::from);
+      let debounce = watch.debounce.map_or(Duration::from_millis(200), |debounce| debounce.0);
+      let mut watcher = Watcher::start(&paths, &watch.ignore.clone().unwrap_or_default(), &base, debounce)
+        .unwrap_or_else(|err| CONSOLE.exit(format!("Failed to watch {}: {}", escape_markup(&paths.join(", ")), escape_markup(&err))));
+
+      // the command runs in its own process group so that restarts stop everything it started,
+      // which means Ctrl-C and a closed terminal only reach ctr and have to be passed on
+      group::catch_interrupts();
+
+      let queue = watch.on_change == Some(WatchAction::Queue);
+      let log_writer = self.open_log(options);
+
+      loop {
+        // reading from the terminal would stop a command in a background process group with SIGTTIN
+        let mut command = self.prepare_command(options, args);
+        command.process_group(0).stdin(Stdio::null());
+
+        if log_writer.is_some() {
+          command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
 
-        if let Some(ref run_as) = options.run_as {
-          if !run_as.sudo.unwrap_or(false) {
-            if let Some(gid) = self.get_group_id(&run_as.group) {
-              command.gid(gid);
+        let mut child = command.spawn().unwrap_or_else(|err| CONSOLE.exit(format!("Failed to run `{}`: {err}", args.join(" "))));
+        let mut tees = log_writer.as_ref().map_or(vec![], |writer| self.tee_output(&mut child, writer));
+        let mut running = true;
+        let mut pending = false;
+
+        loop {
+          if group::interrupted() {
+            if running {
+              let _ = Self::stop_child(&mut child, options.stop_signal, options.stop_grace);
             }
 
-            if let Some(uid) = self.get_user_id(&run_as.user) {
-              command.uid(uid);
+            tees.drain(..).for_each(|tee| drop(tee.join()));
+            return 130_i32;
+          }
+
+          if let Ok(Some(status)) = child.try_wait().map(|status| status.filter(|_| running)) {
+            running = false;
+            let code = signals::exit_code(status);
+            tees.drain(..).for_each(|tee| drop(tee.join()));
+
+            if pending {
+              CONSOLE.info("Running again for the changes made while it was running");
+              break;
             }
+
+            CONSOLE.info(format!("`{}` exited with code {code}, waiting for changes", escape_markup(&options.command.to_string())));
           }
+
+          let Some(path) = watcher.next_change(POLL_INTERVAL) else {
+            continue;
+          };
+
+          let changed = escape_markup(&path.strip_prefix(&base).unwrap_or(&path).display().to_string());
+          if running && queue {
+            pending = true;
+            CONSOLE.info(format!("<brightmagenta>{changed}</brightmagenta> changed, running again once the command exits"));
+            continue;
+          }
+
+          CONSOLE.info(format!("<brightmagenta>{changed}</brightmagenta> changed, {}", if running { "restarting" } else { "running again" }));
+          if running {
+            let _ = Self::stop_child(&mut child, options.stop_signal, options.stop_grace);
+          }
+
+          break;
+        }
+
+        tees.drain(..).for_each(|tee| drop(tee.join()));
+      }
+    }
+  }
+
+  mod process {
+    /// Opens the log of the run, which restarts keep writing to.
+    fn open_log(self: &Self, options: &LaunchOptions) -> Option<Arc<Mutex<LogWriter>>> {
+      options.log.as_ref().map(|log| match LogWriter::open(log) {
+        Ok(writer) => Arc::new(Mutex::new(writer)),
+        Err(err) => CONSOLE.exit(format!("Failed to open the log <brightmagenta>{}</brightmagenta>: {err}", log.path.display())),
+      })
+    }
+
+    /// Starts copying the piped output of `child` to the terminal and the log.
+    fn tee_output(self: &Self, child: &mut Child, writer: &Arc<Mutex<LogWriter>>) -> Vec<std::thread::JoinHandle<()>> {
+      let mut tees = vec![];
+
+      if let Some(stdout) = child.stdout.take() {
+        tees.push(log::tee(stdout, (!self.silent).then(std::io::stdout), "stdout", Arc::clone(writer)));
+      }
+
+      if let Some(stderr) = child.stderr.take() {
+        tees.push(log::tee(stderr, (!self.silent).then(std::io::stderr), "stderr", Arc::clone(writer)));
+      }
+
+      tees
+    }
+
+    /// Waits for `child`. Once `deadline` has passed, or ctr received SIGTERM or SIGHUP, the process group
+    /// of the command receives the stop signal, followed by SIGKILL if it is still running after the grace period.
+    /// Returns the exit status and whether the command timed out.
+    fn wait_until(self: &Self, child: &mut Child, deadline: Option<Instant>, options: &LaunchOptions) -> std::io::Result<(ExitStatus, bool)> {
+      loop {
+        if let Some(status) = signals::try_wait(child)? {
+          return Ok((status, false));
+        }
+
+        if signals::stop_requested() {
+          return Self::stop_child(child, options.stop_signal, options.stop_grace).map(|status| (status, false));
         }
 
-        command.args(&args[1..]);
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+          CONSOLE.warn(format!("The command timed out, sending <yellow>{}</yellow>", options.stop_signal));
+          return Self::stop_child(child, options.stop_signal, options.timeout_grace).map(|status| (status, true));
+        }
+
+        sleep(POLL_INTERVAL);
+      }
+    }
+
+    /// Sends `signal` to the process group of `child`, followed by SIGKILL if it is still running after `grace`.
+    fn stop_child(child: &mut Child, signal: Signal, grace: Duration) -> std::io::Result<ExitStatus> {
+      signals::signal_group(child, signal.0);
+
+      // a suspended command only handles the signal once it is resumed
+      signals::signal_group(child, libc::SIGCONT);
+
+      let kill_at = Instant::now().checked_add(grace).unwrap_or_else(Instant::now);
+      while Instant::now() < kill_at {
+        if let Some(status) = signals::try_wait(child)? {
+          return Ok(status);
+        }
+
+        sleep(POLL_INTERVAL);
+      }
+
+      CONSOLE.warn(format!("The command is still running after {}, sending <yellow>SIGKILL</yellow>", HumanDuration(grace)));
+      signals::signal_group(child, libc::SIGKILL);
+      let status = child.wait();
+      signals::forget_child();
+      status
+    }
+  }
+
+  mod implementation {
+    fn scope(self: &Self) -> PresetScope {
+      match (self.local, self.global) {
+        (true, _) => PresetScope::Local,
+        (_, true) => PresetScope::Global,
+        _ => PresetScope::Any,
+      }
+    }
+
+    /// The file of the preset named by the first argument, unless there is none or `--ignore-config` is set.
+    fn preset_file(self: &Self) -> Option<PathBuf> {
+      self.args.first()
+        .filter(|_| !self.ignore_config)
+        .and_then(|name| locate_preset(name, self.scope()))
+    }
+
+    /// The preset named by the first argument, unless there is none or `--ignore-config` is set.
+    fn preset_name(self: &Self) -> Option<String> {
+      self.preset_file().and(self.args.first().cloned())
+    }
+
+    pub fn get_configs(self: &Self) -> LaunchConfig {
+      let mut default_config = LaunchConfig::default();
 
-        if self.silent {
-          command.stdout(std::process::Stdio::null());
-          command.stderr(std::process::Stdio::null());
+      let Some(ref config_path) = self.preset_file() else {
+        if self.scope() != PresetScope::Any && !self.ignore_config {
+          let scope = if self.local { "local" } else { "global" };
+          CONSOLE.exit(format!("There is no {scope} preset named <brightmagenta>{}</brightmagenta>", self.args[0]));
         }
 
-        match command.spawn() {
-          Ok(_) if self.daemonize => std::process::exit(0),
-          Ok(mut child) => {
-            match child.wait() {
-              Ok(status) => std::process::exit(status.code().unwrap_or(1)),
-              Err(err) => CONSOLE.exit(format!("Failed to wait for `{}`: {err}", args.join(" ")))
+        if !self.args.is_empty() && default_config.general.command.is_none() {
+          default_config.general.command = Some(
+            if self.no_shell {
+              LaunchCommand::Exec(self.args.to_owned())
+            } else {
+              LaunchCommand::Shell(self.shell_script())
             }
+          );
+        }
+
+        self.help.then(|| Self::usage(0));
+        if !self.param.is_empty() {
+          CONSOLE.exit("Parameters can only be passed to presets");
+        }
+
+        return default_config;
+      };
+
+      let preset = &self.args[0];
+      let mut config = LaunchConfig::resolve(config_path);
+      let params = config.params.clone().unwrap_or_default();
+
+      let mut arguments = PresetArguments::parse(&self.args[1..], &self.param, &params);
+      (self.help || arguments.help).then(|| self.preset_usage(preset, &params));
+
+      arguments.resolve_params(preset, &params);
+      config.general.command = config.general.command.map(|command| arguments.render(command));
+
+      config
+    }
+
+    fn preset_usage(self: &Self, preset: &str, params: &ParamMap) {
+      CONSOLE.print_usage::<Self>(vec![
+        ArgumentType::Flags,
+        ArgumentType::Operand { name: preset.to_owned() },
+        ArgumentType::Variadic { name: "args".to_owned() },
+      ]);
+
+      let mut operations = vec![std_v2::command::Command::help_flag()];
+      for (name, param) in params {
+        let example = match (param.choices.as_ref(), param.default.as_ref()) {
+          (Some(choices), _) => choices.join("|"),
+          (None, Some(default)) => default.to_owned(),
+          (None, None) => "value".to_owned(),
+        };
+
+        let mut about = param.description.clone().unwrap_or_default();
+        if param.required.unwrap_or(false) && param.default.is_none() {
+          about = format!("{about} <brightblack>(required)</brightblack>").trim_start().to_owned();
+        }
+
+        operations.push(std_v2::command::Command::option(' ', name, example, about));
+      }
+
+      CONSOLE.print_operation_collection(vec![operations]);
+      std::process::exit(0);
+    }
+
+    /// Builds the argv for `command`, including the shell and the prefix of the `run_as.elevate` tool.
+    fn command_args(self: &Self, options: &LaunchOptions, command: &LaunchCommand) -> Vec<String> {
+      let login = options.identity.as_ref().filter(|identity| *identity.login_shell());
+      let mut args = match (command, login) {
+        (LaunchCommand::Shell(script), None) => vec![options.shell.to_owned(), "-c".to_owned(), script.to_owned()],
+        (LaunchCommand::Exec(argv), _) if argv.is_empty() => CONSOLE.exit("No binary specified"),
+        (LaunchCommand::Exec(argv), None) => argv.to_owned(),
+        (LaunchCommand::Shell(script), Some(identity)) => vec![Self::login_shell(options, identity), "-l".to_owned(), "-c".to_owned(), script.to_owned()],
+        // the shell only loads the profile, `$0` and `$@` keep the arguments exactly as they were given
+        (LaunchCommand::Exec(argv), Some(identity)) => {
+          let mut wrapped = vec![Self::login_shell(options, identity), "-l".to_owned(), "-c".to_owned(), "exec \"$0\" \"$@\"".to_owned()];
+          wrapped.extend(argv.iter().cloned());
+          wrapped
+        },
+      };
+
+      if let (Some(run_as), Some(tool)) = (options.run_as.as_ref(), options.elevate) {
+        args = tool.wrap(run_as, args).unwrap_or_else(|err| CONSOLE.exit(format!("Invalid <brightblue>[run_as]</brightblue> section, {err}")));
+      }
+
+      args
+    }
+
+    /// Sets up the environment of the command, which `--dry-run` prints as well.
+    fn apply_environment(options: &LaunchOptions, command: &mut Command) {
+      if !options.preserve_env {
+        command.env_clear();
+      }
+
+      // only meant for this `ctr run` itself, not for nested runs in the command, which would
+      // skip their dependencies or take the id of the job they are started from
+      for var in [SKIP_DEPENDENCIES_VAR, JOB_ID_VAR, RESTART_ID_VAR] {
+        command.env_remove(var);
+      }
+
+      if let Some(target) = options.identity.as_ref().filter(|identity| !identity.is_current()) {
+        command.envs(target.environment());
+      }
+
+      for key in options.unset_environment() {
+        command.env_remove(key);
+      }
+
+      command.envs(options.environment_vars());
+    }
+
+    /// Creates the process for `args` with the identity, working directory and environment of the preset.
+    fn prepare_command(self: &Self, options: &LaunchOptions, args: &[String]) -> Command {
+      let Some((binary, arguments)) = args.split_first() else {
+        CONSOLE.exit("No binary specified")
+      };
+
+      let mut command = options.sandbox.as_ref().map_or_else(|| Command::new(binary), |sandbox| sandbox.command(binary));
+
+      command.args(arguments);
+
+      if let Some(ref current_dir) = options.current_dir {
+        if !Path::new(current_dir).is_dir() {
+          CONSOLE.exit(format!("The working directory <brightmagenta>{current_dir}</brightmagenta> does not exist"));
+        }
+
+        command.current_dir(current_dir);
+      }
+
+      Self::apply_environment(options, &mut command);
+
+      if self.silent {
+        command.stdout(std::process::Stdio::null());
+        command.stderr(std::process::Stdio::null());
+      }
+
+      if !options.limits.is_empty() {
+        let limits = options.limits.clone();
+
+        // SAFETY: `ProcessLimits::apply` only performs async-signal-safe syscalls
+        unsafe {
+          command.pre_exec(move || limits.apply());
+        }
+      }
+
+      // switched after the limits, since a negative `nice` or raised hard limits need the privileges of the current user
+      if let Some(target) = options.identity.as_ref().filter(|identity| !identity.is_current()) {
+        let switched = target.clone();
+
+        // SAFETY: `Identity::apply` only performs async-signal-safe syscalls
+        unsafe {
+          command.pre_exec(move || switched.apply());
+        }
+      }
+
+      // entered last, since the identity from `run_as` and the limits may need privileges of the host
+      if let Some(ref sandbox) = options.sandbox {
+        let isolation = sandbox.clone();
+
+        // SAFETY: `Sandbox::apply` only performs async-signal-safe syscalls
+        unsafe {
+          command.pre_exec(move || isolation.apply());
+        }
+      }
+
+      command
+    }
+
+    /// Runs the hooks of one stage in order.
+    /// Returns the exit code to abort with, if a hook with the `abort` policy failed.
+    fn run_hooks(self: &Self, options: &LaunchOptions, stage: &str, hooks: &Option<Vec<LaunchHook>>, env: &[(String, String)]) -> Option<i32> {
+      for hook in hooks.iter().flatten() {
+        let args = self.command_args(options, hook.command());
+        let mut command = self.prepare_command(options, &args);
+        command.envs(env.iter().cloned());
+
+        let (code, reason) = match command.status() {
+          Ok(status) if status.success() => continue,
+          Ok(status) => (signals::exit_code(status), format!("{status}")),
+          Err(err) => (1_i32, format!("{err}")),
+        };
+
+        let message = format!("The <brightblue>{stage}</brightblue> hook `{}` failed: {}", escape_markup(&hook.command().to_string()), escape_markup(&reason));
+        match hook.policy() {
+          HookPolicy::Abort => {
+            CONSOLE.error(message);
+            return Some(code);
           },
-          Err(err) => CONSOLE.exit(format!("Failed to run `{}`: {err}", args.join(" ")))
+          HookPolicy::Warn => CONSOLE.warn(message),
+          HookPolicy::Ignore => {},
         }
+      }
+
+      None
+    }
+
+    /// Runs the command with its hooks, restarts and timeout, and returns the exit code for ctr.
+    pub fn launch(self: &Self, options: &LaunchOptions) -> i32 {
+      let args = self.command_args(options, &options.command);
+      let hooks = &options.hooks;
+
+      if let Some(ref sandbox) = options.sandbox {
+        if let Some(tool) = options.elevate {
+          CONSOLE.exit(format!("<brightblue>run_as.elevate</brightblue> cannot be combined with <brightblue>[sandbox]</brightblue>, {tool} does not work inside user namespaces"));
+        }
+
+        sandbox.check_supported();
+      }
+
+      if let Some(ref identity) = options.identity {
+        if let Err(reason) = identity.check_permission() {
+          CONSOLE.exit(reason);
+        }
+      }
+
+      for (name, limit, max) in options.limits.above_hard_limits() {
+        CONSOLE.warn(format!(
+          "<brightblue>limits.{name}</brightblue> = {limit} is above the hard limit of {max}, which is used instead. Set <brightblue>limits.hard = true</brightblue> to raise it"
+        ));
+      }
+
+      if let Some(code) = self.run_hooks(options, "before", &hooks.before, &[]) {
+        return code;
+      }
+
+      if options.daemonize {
+        self.detach(options, &args);
+      }
+
+      if let Some(ref watch) = options.watch {
+        return self.run_watched(options, &args, watch);
+      }
+
+      let started = Instant::now();
+      let deadline = options.timeout.and_then(|timeout| started.checked_add(timeout));
+      let mut restarts: u32 = 0;
+      let log_writer = self.open_log(options);
+
+      let (status, timed_out) = loop {
+        let mut command = self.prepare_command(options, &args);
+        let terminal = options.tty.then(|| Pty::open().unwrap_or_else(|err| CONSOLE.exit(format!("Failed to open a pseudo-terminal: {err}"))));
+
+        // the whole process group is stopped on timeout and signals, see `wait_until`
+        match terminal {
+          Some(ref pty) => pty.attach(&mut command).unwrap_or_else(|err| CONSOLE.exit(format!("Failed to open a pseudo-terminal: {err}"))),
+          None => signals::own_process_group(&mut command),
+        }
+
+        if log_writer.is_some() && terminal.is_none() {
+          command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+
+        let mut child = command.spawn().unwrap_or_else(|err| CONSOLE.exit(format!("Failed to run `{}`: {err}", args.join(" "))));
+
+        // closes the copies of the pseudo-terminal it holds, see `Pty::forward`
+        drop(command);
+
+        let tees = match terminal {
+          Some(pty) => pty.forward((!self.silent).then(std::io::stdout), log_writer.clone())
+            .unwrap_or_else(|err| CONSOLE.exit(format!("Failed to read from the pseudo-terminal: {err}"))),
+          None => log_writer.as_ref().map_or(vec![], |writer| self.tee_output(&mut child, writer)),
+        };
+
+        signals::give_terminal(&child);
+        signals::forward_signals(&child);
+        if options.tty {
+          pty::follow_window_size();
+        }
+
+        let waited = self.wait_until(&mut child, deadline, options);
+        signals::take_terminal();
+        pty::restore_mode();
+
+        let (status, timed_out) = waited.unwrap_or_else(|err| CONSOLE.exit(format!("Failed to wait for `{}`: {err}", args.join(" "))));
+
+        for tee in tees {
+          let _ = tee.join();
+        }
+
+        if timed_out || signals::stop_requested() || signals::interrupted(status) || !options.should_restart(&status, restarts) {
+          break (status, timed_out);
+        }
+
+        restarts = restarts.saturating_add(1);
+        let delay = options.restart_backoff(restarts);
+        let attempts = options.max_restarts.map_or(restarts.to_string(), |max| format!("{restarts}/{max}"));
+
+        CONSOLE.warn(format!(
+          "`{}` exited with code {}, restarting in {} <brightblack>(attempt {attempts})</brightblack>",
+          escape_markup(&options.command.to_string()),
+          signals::exit_code(status),
+          HumanDuration(delay)
+        ));
+
+        if !signals::sleep_unless_stopped(delay, POLL_INTERVAL) {
+          break (status, false);
+        }
+      };
+
+      let code = if timed_out {
+        TIMEOUT_EXIT_CODE
       } else {
-        CONSOLE.exit("No binary specified")
+        signals::exit_code(status)
+      };
+
+      let env = vec![
+        ("CTR_EXIT_CODE".to_owned(), code.to_string()),
+        ("CTR_DURATION_MS".to_owned(), started.elapsed().as_millis().to_string()),
+      ];
+
+      let outcome = if status.success() && !timed_out {
+        self.run_hooks(options, "on_success", &hooks.on_success, &env)
+      } else {
+        self.run_hooks(options, "on_failure", &hooks.on_failure, &env)
+      };
+
+      // `after` runs even if an `on_success` or `on_failure` hook aborted, whose exit code wins
+      let after = self.run_hooks(options, "after", &hooks.after, &env);
+      let aborted = outcome.or(after);
+
+      let exit_code = aborted.unwrap_or(code);
+      self.notify_finished(options, exit_code, started.elapsed());
+
+      exit_code
+    }
+
+    /// Adds the run to the history, see `ctr history`. `code` is `None` for daemonized commands.
+    fn record_history(self: &Self, options: &LaunchOptions, started: u64, code: Option<i32>) {
+      let mut entry = HistoryEntry {
+        preset: self.preset_name(),
+        command: options.command.to_string(),
+        argv: std::env::args_os().map(|arg| arg.to_string_lossy().to_string()).collect(),
+        cwd: std::env::current_dir().unwrap_or_default(),
+        started,
+        ended: code.map(|_| history::now_millis()),
+        exit_code: code,
+        daemonized: code.is_none(),
+        ..HistoryEntry::default()
+      };
+
+      entry.record();
+    }
+
+    /// Shows a desktop notification about the finished command, if `notify` asks for it.
+    fn notify_finished(self: &Self, options: &LaunchOptions, code: i32, elapsed: Duration) {
+      let Some(ref notify) = options.notify else {
+        return;
+      };
+
+      let wanted = match notify.on.unwrap_or_default() {
+        NotifyOn::Always => true,
+        NotifyOn::Failure => code != 0_i32,
+        NotifyOn::Success => code == 0_i32,
+      };
+
+      if !wanted || notify.min_duration.is_some_and(|min| elapsed < min.0) {
+        return;
+      }
+
+      let name = self.preset_name().unwrap_or_else(|| options.command.to_string());
+      let elapsed = PsCommand::format_uptime(elapsed);
+      let (body, urgency) = if code == 0_i32 {
+        (format!("{name} finished after {elapsed}"), notify_rust::Urgency::Normal)
+      } else {
+        (format!("{name} failed with exit code {code} after {elapsed}"), notify_rust::Urgency::Critical)
+      };
+
+      if let Err(err) = NotifyCommand::show(BINARY_NAME, &body, Some(urgency), None) {
+        CONSOLE.warn(format!("Failed to show the notification: {err}"));
       }
     }
   }
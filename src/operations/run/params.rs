@@ -0,0 +1,407 @@
+use std::collections::HashMap;
+
+use std_v2::{console::CONSOLE, string::StringV2, struct_gen};
+
+use super::{
+  ser::{LaunchCommand, ParamMap},
+  Options,
+};
+
+/// A piece of a preset command, either literal text or a `{placeholder}`.
+enum Segment {
+  Text(String),
+  Placeholder(String),
+}
+
+/// Splits `template` into text and placeholders.
+/// Braces directly after a `$` are left alone, so `${VAR}` still reaches the shell.
+fn segments(template: &str) -> Vec<Segment> {
+  let mut segments = vec![];
+  let mut text = String::new();
+  let mut chars = template.chars().peekable();
+  let mut previous = None;
+
+  while let Some(c) = chars.next() {
+    if c == '{' && previous != Some('$') {
+      let mut key = String::new();
+      let mut closed = false;
+
+      while let Some(&next) = chars.peek() {
+        if next == '}' {
+          chars.next();
+          closed = true;
+          break;
+        }
+
+        if next == '{' || next.is_whitespace() {
+          break;
+        }
+
+        key.push(next);
+        chars.next();
+      }
+
+      if closed && !key.is_empty() {
+        segments.push(Segment::Text(std::mem::take(&mut text)));
+        segments.push(Segment::Placeholder(key));
+        previous = Some('}');
+        continue;
+      }
+
+      text.push(c);
+      text.push_str(&key);
+      previous = key.chars().last().or(Some(c));
+      continue;
+    }
+
+    text.push(c);
+    previous = Some(c);
+  }
+
+  segments.push(Segment::Text(text));
+  segments
+}
+
+/// The shell quotes a placeholder is in, which decides how its value is escaped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Quoting {
+  Unquoted,
+  Single,
+  Double,
+}
+
+impl Quoting {
+  /// The quotes that are still open after `text`.
+  fn after(self, text: &str) -> Self {
+    let mut quoting = self;
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+      quoting = match (quoting, c) {
+        (Self::Unquoted | Self::Double, '\\') => {
+          chars.next();
+          quoting
+        },
+        (Self::Unquoted, '\'') => Self::Single,
+        (Self::Unquoted, '"') => Self::Double,
+        (Self::Single, '\'') | (Self::Double, '"') => Self::Unquoted,
+        _ => quoting,
+      };
+    }
+
+    quoting
+  }
+
+  /// Escapes `value` so that the shell reads it as a single word, without closing the quotes it is in.
+  fn escape(self, value: &str) -> String {
+    match self {
+      Self::Unquoted => Options::quote_arg(value),
+      Self::Single => value.replace('\'', "'\\''"),
+      Self::Double => value.chars().fold(String::new(), |mut escaped, c| {
+        if matches!(c, '"' | '\\' | '$' | '`') {
+          escaped.push('\\');
+        }
+
+        escaped.push(c);
+        escaped
+      }),
+    }
+  }
+}
+
+struct_gen! {
+  /// Arguments that follow the preset name on the command line.
+  pub struct PresetArguments {
+    pub let positional: Vec<String> = Vec::new();
+    pub let named: HashMap<String, String> = HashMap::new();
+    pub let help: bool = false;
+  }
+
+  mod constructors {
+    /// Separates positional arguments from `--param name=value`, `--name value` and `--help`.
+    /// Everything after `--` is positional.
+    pub fn parse(args: &[String], cli_params: &[String], params: &ParamMap) -> Self {
+      let mut arguments = Self::default();
+
+      for assignment in cli_params {
+        arguments.assign(assignment);
+      }
+
+      let mut args = args.iter();
+      while let Some(arg) = args.next() {
+        match arg.as_str() {
+          "--" => {
+            arguments.positional.extend(args.by_ref().cloned());
+          },
+          "-H" | "--help" => arguments.help = true,
+          "-p" | "--param" => match args.next() {
+            Some(assignment) => arguments.assign(assignment),
+            None => CONSOLE.exit("The argument <brightmagenta>--param</brightmagenta> requires a value."),
+          },
+          _ => {
+            if let Some(assignment) = arg.strip_prefix("--param=") {
+              arguments.assign(assignment);
+              continue;
+            }
+
+            if let Some(flag) = arg.strip_prefix("--") {
+              let (name, inline_value) = match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_owned())),
+                None => (flag, None),
+              };
+
+              if params.contains_key(name) {
+                let value = inline_value.or_else(|| args.next().cloned())
+                  .unwrap_or_else(|| CONSOLE.exit(format!("The argument <brightmagenta>--{name}</brightmagenta> requires a value.")));
+
+                arguments.named.insert(name.to_owned(), value);
+                continue;
+              }
+            }
+
+            arguments.positional.push(arg.to_owned());
+          }
+        }
+      }
+
+      arguments
+    }
+  }
+
+  mod implementation {
+    fn assign(self: &mut Self, assignment: &str) {
+      match assignment.split_once('=') {
+        Some((name, value)) => {
+          self.named.insert(name.trim().to_owned(), value.to_owned());
+        },
+        None => CONSOLE.exit(format!("Invalid parameter <brightmagenta>{assignment}</brightmagenta>, expected <brightblue>name=value</brightblue>")),
+      }
+    }
+
+    /// Applies defaults and checks every named parameter against its declaration.
+    pub fn resolve_params(self: &mut Self, preset: &str, params: &ParamMap) {
+      for name in self.named.keys() {
+        if !params.contains_key(name) {
+          CONSOLE.exit(match StringV2::from(name).nearest(params.keys().cloned().collect()) {
+            Some(suggestion) => format!("The preset <brightmagenta>{preset}</brightmagenta> has no parameter <brightblue>{name}</brightblue>. Did you mean <brightblue>{suggestion}</brightblue>?"),
+            None => format!("The preset <brightmagenta>{preset}</brightmagenta> has no parameter <brightblue>{name}</brightblue>"),
+          });
+        }
+      }
+
+      for (name, param) in params {
+        if !self.named.contains_key(name) {
+          match param.default {
+            Some(ref default) => {
+              self.named.insert(name.to_owned(), default.to_owned());
+            },
+            None if param.required.unwrap_or(false) => {
+              CONSOLE.exit(format!("The preset <brightmagenta>{preset}</brightmagenta> requires the parameter <brightblue>{name}</brightblue>"));
+            },
+            None => {},
+          }
+        }
+
+        if let (Some(value), Some(choices)) = (self.named.get(name), param.choices.as_ref()) {
+          if !choices.contains(value) {
+            CONSOLE.exit(format!("<brightmagenta>{value}</brightmagenta> is not a valid value for <brightblue>{name}</brightblue>. Valid values are: {}", choices.join(", ")));
+          }
+        }
+      }
+    }
+
+    /// Substitutes `{1}`, `{2}`, `{@}` and `{name}` in the preset command.
+    /// Shell scripts get quoted values. If the command has no placeholders, the arguments are appended.
+    pub fn render(self: &Self, command: LaunchCommand) -> LaunchCommand {
+      let templates = match command {
+        LaunchCommand::Shell(ref script) => vec![script.to_owned()],
+        LaunchCommand::Exec(ref argv) => argv.to_owned(),
+      };
+
+      let mut has_placeholders = false;
+      let mut uses_rest = false;
+      let mut highest_index = 0;
+      for template in templates.iter() {
+        for segment in segments(template) {
+          if let Segment::Placeholder(key) = segment {
+            match key.parse::<usize>() {
+              Ok(index) => {
+                highest_index = highest_index.max(index);
+                has_placeholders = true;
+              },
+              Err(_) if key == "@" => {
+                uses_rest = true;
+                has_placeholders = true;
+              },
+              Err(_) => has_placeholders |= self.named.contains_key(&key),
+            }
+          }
+        }
+      }
+
+      if !has_placeholders {
+        return match command {
+          LaunchCommand::Shell(script) if !self.positional.is_empty() => {
+            LaunchCommand::Shell(format!("{script} {}", self.quoted(&self.positional)))
+          },
+          LaunchCommand::Exec(mut argv) => {
+            argv.extend(self.positional.iter().cloned());
+            LaunchCommand::Exec(argv)
+          },
+          command => command,
+        };
+      }
+
+      if self.positional.len() < highest_index {
+        CONSOLE.exit(format!("Expected at least {highest_index} arguments, but got {}", self.positional.len()));
+      }
+
+      if !uses_rest && self.positional.len() > highest_index {
+        CONSOLE.exit(format!(
+          "Expected at most {highest_index} arguments, but got {}. Add <brightblue>{{@}}</brightblue> to the command to pass on the rest",
+          self.positional.len()
+        ));
+      }
+
+      let rest = self.positional.iter().skip(highest_index).cloned().collect::<Vec<String>>();
+
+      match command {
+        LaunchCommand::Shell(script) => LaunchCommand::Shell(self.substitute(&script, &rest, true)),
+        LaunchCommand::Exec(argv) => LaunchCommand::Exec(
+          argv.iter()
+            .flat_map(|arg| {
+              if arg == "{@}" {
+                rest.clone()
+              } else {
+                vec![self.substitute(arg, &rest, false)]
+              }
+            })
+            .collect()
+        ),
+      }
+    }
+
+    /// Replaces the placeholders in `template`. With `quote`, the template is a shell script
+    /// and values are escaped for the quotes they are in, so `"{name}"` keeps working.
+    fn substitute(self: &Self, template: &str, rest: &[String], quote: bool) -> String {
+      let mut quoting = Quoting::Unquoted;
+      let mut output = String::new();
+
+      for segment in segments(template) {
+        let key = match segment {
+          Segment::Text(text) => {
+            if quote {
+              quoting = quoting.after(&text);
+            }
+
+            output.push_str(&text);
+            continue;
+          },
+          Segment::Placeholder(key) => key,
+        };
+
+        let value = if key == "@" {
+          match quoting {
+            _ if !quote => rest.join(" "),
+            Quoting::Unquoted => self.quoted(rest),
+            quotes => quotes.escape(&rest.join(" ")),
+          }
+        } else {
+          let positional = key.parse::<usize>().ok().and_then(|index| index.checked_sub(1)).and_then(|position| self.positional.get(position));
+          match positional.or_else(|| self.named.get(&key)) {
+            Some(value) if quote => quoting.escape(value),
+            Some(value) => value.to_owned(),
+            None => format!("{{{key}}}"),
+          }
+        };
+
+        output.push_str(&value);
+      }
+
+      output
+    }
+
+    fn quoted(self: &Self, args: &[String]) -> String {
+      args.iter().map(|arg| Options::quote_arg(arg)).collect::<Vec<String>>().join(" ")
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashMap;
+
+  use super::{segments, PresetArguments, Segment};
+  use crate::operations::run::ser::LaunchCommand;
+
+  fn arguments(positional: &[&str], named: &[(&str, &str)]) -> PresetArguments {
+    PresetArguments {
+      positional: positional.iter().map(|arg| (*arg).to_owned()).collect(),
+      named: named.iter().map(|(name, value)| ((*name).to_owned(), (*value).to_owned())).collect::<HashMap<String, String>>(),
+      help: false,
+    }
+  }
+
+  fn shell(args: &PresetArguments, script: &str) -> String {
+    match args.render(LaunchCommand::Shell(script.to_owned())) {
+      LaunchCommand::Shell(rendered) => rendered,
+      LaunchCommand::Exec(argv) => panic!("expected a shell command, got {argv:?}"),
+    }
+  }
+
+  fn exec(args: &PresetArguments, argv: &[&str]) -> Vec<String> {
+    match args.render(LaunchCommand::Exec(argv.iter().map(|arg| (*arg).to_owned()).collect())) {
+      LaunchCommand::Exec(rendered) => rendered,
+      LaunchCommand::Shell(script) => panic!("expected an argv, got {script}"),
+    }
+  }
+
+  #[test]
+  fn segments_split_placeholders_but_not_variables() {
+    let parts = segments("a {1} ${HOME} {not closed {name}}")
+      .into_iter()
+      .map(|segment| match segment {
+        Segment::Text(text) => format!("text:{text}"),
+        Segment::Placeholder(key) => format!("key:{key}"),
+      })
+      .collect::<Vec<String>>();
+
+    assert_eq!(parts, ["text:a ", "key:1", "text: ${HOME} {not closed ", "key:name", "text:}"]);
+  }
+
+  #[test]
+  fn substitute_escapes_for_the_surrounding_quotes() {
+    let args = arguments(&[], &[("name", "it's \"$x\"")]);
+
+    assert_eq!(args.substitute("echo {name}", &[], true), r#"echo 'it'\''s "$x"'"#);
+    assert_eq!(args.substitute(r#"echo "hi {name}""#, &[], true), r#"echo "hi it's \"\$x\"""#);
+    assert_eq!(args.substitute("echo 'hi {name}'", &[], true), r#"echo 'hi it'\''s "$x"'"#);
+    assert_eq!(args.substitute(r#"echo "\"" {name}"#, &[], true), r#"echo "\"" 'it'\''s "$x"'"#);
+    assert_eq!(args.substitute("--name={name}", &[], false), r#"--name=it's "$x""#);
+  }
+
+  #[test]
+  fn substitute_passes_the_rest_on() {
+    let args = arguments(&["a", "b c"], &[]);
+    let rest = ["b c".to_owned()];
+
+    assert_eq!(args.substitute("run {1} {@}", &rest, true), "run a 'b c'");
+    assert_eq!(args.substitute(r#"echo "{@}""#, &rest, true), r#"echo "b c""#);
+  }
+
+  #[test]
+  fn render_appends_arguments_without_placeholders() {
+    let args = arguments(&["x y"], &[]);
+
+    assert_eq!(shell(&args, "echo"), "echo 'x y'");
+    assert_eq!(exec(&args, &["echo"]), ["echo", "x y"]);
+  }
+
+  #[test]
+  fn render_fills_placeholders() {
+    let args = arguments(&["one", "two", "three"], &[("mode", "fast")]);
+
+    assert_eq!(shell(&args, r#"run "{1}" --mode={mode} {@}"#), r#"run "one" --mode=fast two three"#);
+    assert_eq!(exec(&args, &["run", "{1}", "{@}"]), ["run", "one", "two", "three"]);
+  }
+}
@@ -0,0 +1,261 @@
+use std::{
+  ffi::CStr,
+  fs::{File, OpenOptions},
+  io::{Read, Write},
+  os::{
+    fd::{AsRawFd, FromRawFd},
+    unix::{fs::OpenOptionsExt, process::CommandExt},
+  },
+  process::Command,
+  sync::{
+    atomic::{AtomicI32, Ordering},
+    Arc, Mutex,
+  },
+  thread::JoinHandle,
+};
+
+use std_v2::console::CONSOLE;
+
+use super::log::LogWriter;
+
+/// The master side of the pseudo-terminal of the running command, -1 if there is none.
+static MASTER: AtomicI32 = AtomicI32::new(-1);
+/// The settings of the terminal on stdin from before it was put into raw mode.
+static SAVED_MODE: Mutex<Option<libc::termios>> = Mutex::new(None);
+
+/// How long the thread passing on stdin waits for input before checking whether the command is done.
+const STDIN_POLL_MS: libc::c_int = 100;
+
+/// A pseudo-terminal for the command, so that it keeps colors and line buffering
+/// while ctr copies its output to the terminal, the log or the prefixed output of a group.
+pub struct Pty {
+  master: File,
+  slave: File,
+}
+
+impl Pty {
+  pub fn open() -> std::io::Result<Self> {
+    // SAFETY: `posix_openpt` has no memory safety requirements
+    let fd = unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC) };
+    if fd < 0_i32 {
+      return Err(std::io::Error::last_os_error());
+    }
+
+    // SAFETY: `fd` was just opened and is not owned by anything else
+    let master = unsafe { File::from_raw_fd(fd) };
+
+    // SAFETY: `name` is valid for writes of its whole length, and `ptsname_r` terminates it on success
+    let mut name = [libc::c_char::default(); 128];
+    let path = unsafe {
+      if libc::grantpt(fd) != 0_i32 || libc::unlockpt(fd) != 0_i32 {
+        return Err(std::io::Error::last_os_error());
+      }
+
+      let err = libc::ptsname_r(fd, name.as_mut_ptr(), name.len());
+      if err != 0_i32 {
+        return Err(std::io::Error::from_raw_os_error(err));
+      }
+
+      CStr::from_ptr(name.as_ptr()).to_string_lossy().to_string()
+    };
+
+    let slave = OpenOptions::new().read(true).write(true).custom_flags(libc::O_NOCTTY).open(path)?;
+    copy_window_size(fd);
+
+    Ok(Self { master, slave })
+  }
+
+  /// Connects the output of `command` to the pseudo-terminal, and its input as well if stdin of ctr is a terminal.
+  /// The command starts a new session with the pseudo-terminal as its controlling terminal,
+  /// which also makes it the leader of its own process group.
+  pub fn attach(&self, command: &mut Command) -> std::io::Result<()> {
+    command.stdout(self.slave.try_clone()?).stderr(self.slave.try_clone()?);
+    if stdin_is_terminal() {
+      command.stdin(self.slave.try_clone()?);
+    }
+
+    // SAFETY: the closure only performs async-signal-safe syscalls
+    unsafe {
+      command.pre_exec(|| {
+        if libc::setsid() == -1_i32 || libc::ioctl(libc::STDOUT_FILENO, libc::TIOCSCTTY, 0_i32) == -1_i32 {
+          return Err(std::io::Error::last_os_error());
+        }
+
+        Ok(())
+      });
+    }
+
+    Ok(())
+  }
+
+  /// Starts copying the output of the spawned command to `terminal` (if any) and the log,
+  /// and the input of ctr to the command. Stdin is put into raw mode meanwhile, see [`restore_mode`].
+  pub fn forward<W>(self, terminal: Option<W>, writer: Option<Arc<Mutex<LogWriter>>>) -> std::io::Result<Vec<JoinHandle<()>>>
+  where
+    W: Write + Send + 'static,
+  {
+    let Self { master, slave } = self;
+
+    // the command holds its own copies, reading from the master fails once all of them are closed
+    drop(slave);
+
+    MASTER.store(master.as_raw_fd(), Ordering::SeqCst);
+    let mut threads = vec![];
+
+    if stdin_is_terminal() {
+      let input = master.try_clone()?;
+      set_raw_mode();
+      threads.push(std::thread::spawn(move || forward_input(input)));
+    }
+
+    threads.insert(0, std::thread::spawn(move || forward_output(master, terminal, writer)));
+    Ok(threads)
+  }
+}
+
+fn forward_output<W: Write>(mut master: File, mut terminal: Option<W>, writer: Option<Arc<Mutex<LogWriter>>>) {
+  let mut buffer = [0_u8; 4096];
+  let mut line = vec![];
+  let mut warned = false;
+
+  // written as it arrives rather than by line, so that prompts and progress bars show up right away
+  while let Ok(read) = master.read(&mut buffer) {
+    let Some(chunk) = buffer.get(..read).filter(|chunk| !chunk.is_empty()) else {
+      break;
+    };
+
+    if let Some(ref mut output) = terminal {
+      let _ = output.write_all(chunk).and_then(|()| output.flush());
+    }
+
+    let Some(ref shared) = writer else {
+      continue;
+    };
+
+    line.extend_from_slice(chunk);
+    while let Some(end) = line.iter().position(|byte| *byte == b'\n') {
+      let mut entry = line.drain(..=end).collect::<Vec<u8>>();
+
+      // the pseudo-terminal turns every newline into CRLF
+      if entry.ends_with(b"\r\n") {
+        entry.remove(entry.len().saturating_sub(2));
+      }
+
+      let result = match shared.lock() {
+        Ok(mut log) => log.write_line("tty", &entry),
+        Err(_) => Ok(()),
+      };
+
+      if let Err(err) = result {
+        if !warned {
+          CONSOLE.warn(format!("Failed to write to the log: {err}"));
+          warned = true;
+        }
+      }
+    }
+  }
+
+  if let (Some(shared), false) = (writer, line.is_empty()) {
+    if let Ok(mut log) = shared.lock() {
+      let _ = log.write_line("tty", &line);
+    }
+  }
+
+  MASTER.store(-1_i32, Ordering::SeqCst);
+}
+
+/// Passes what is typed on stdin on to the command, until its output is closed.
+fn forward_input(mut master: File) {
+  let mut buffer = [0_u8; 1024];
+  let mut stdin = std::io::stdin();
+  let mut poll = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+
+  while MASTER.load(Ordering::SeqCst) >= 0_i32 {
+    // SAFETY: `poll` is valid for reads and writes
+    if unsafe { libc::poll(&mut poll, 1, STDIN_POLL_MS) } <= 0_i32 {
+      continue;
+    }
+
+    match stdin.read(&mut buffer) {
+      Ok(0) | Err(_) => break,
+      Ok(read) => {
+        if master.write_all(buffer.get(..read).unwrap_or_default()).is_err() {
+          break;
+        }
+      },
+    }
+  }
+}
+
+fn stdin_is_terminal() -> bool {
+  // SAFETY: `isatty` has no memory safety requirements
+  unsafe { libc::isatty(libc::STDIN_FILENO) == 1_i32 }
+}
+
+/// Gives the pseudo-terminal `master` the size of the terminal ctr runs in, if there is one.
+fn copy_window_size(master: libc::c_int) {
+  // SAFETY: `size` is plain data and valid for writes, these calls are async-signal-safe
+  unsafe {
+    let mut size: libc::winsize = std::mem::zeroed();
+    for fd in [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+      if libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) == 0_i32 && size.ws_col > 0 {
+        libc::ioctl(master, libc::TIOCSWINSZ, &size);
+        return;
+      }
+    }
+  }
+}
+
+extern "C" fn on_resize(_: libc::c_int) {
+  let master = MASTER.load(Ordering::SeqCst);
+  if master >= 0_i32 {
+    copy_window_size(master);
+  }
+}
+
+/// Resizes the pseudo-terminal whenever the terminal of ctr is resized, after which the kernel sends SIGWINCH
+/// to the command. Replaces the forwarding of SIGWINCH set up by `signals::forward_signals`.
+pub fn follow_window_size() {
+  let handler: extern "C" fn(libc::c_int) = on_resize;
+
+  // SAFETY: the handler only loads an atomic and calls `ioctl`
+  #[allow(clippy::as_conversions, clippy::fn_to_numeric_cast_any)]
+  unsafe {
+    libc::signal(libc::SIGWINCH, handler as libc::sighandler_t);
+  }
+}
+
+/// Puts the terminal on stdin into raw mode, so that every key, including Ctrl-C, reaches the command.
+pub fn set_raw_mode() {
+  let Ok(mut saved) = SAVED_MODE.lock() else {
+    return;
+  };
+
+  // SAFETY: `mode` is plain data and fully initialized by `tcgetattr`
+  unsafe {
+    let mut mode: libc::termios = std::mem::zeroed();
+    if libc::tcgetattr(libc::STDIN_FILENO, &mut mode) != 0_i32 {
+      return;
+    }
+
+    let original = mode;
+    libc::cfmakeraw(&mut mode);
+    if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &mode) == 0_i32 {
+      saved.get_or_insert(original);
+    }
+  }
+}
+
+/// Restores the terminal settings from before [`set_raw_mode`]. Returns whether the terminal was in raw mode.
+pub fn restore_mode() -> bool {
+  let Some(mode) = SAVED_MODE.lock().ok().and_then(|mut saved| saved.take()) else {
+    return false;
+  };
+
+  // SAFETY: `mode` is a valid `termios` returned by `tcgetattr`
+  unsafe {
+    libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &mode);
+  }
+
+  true
+}
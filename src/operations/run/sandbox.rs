@@ -0,0 +1,405 @@
+use std::{
+  ffi::{CStr, CString},
+  os::unix::{ffi::OsStrExt, process::ExitStatusExt},
+  path::{Path, PathBuf},
+  process::{Command, ExitStatus},
+};
+
+use std_v2::{console::CONSOLE, struct_gen};
+
+use super::{ser::LaunchConfigSandbox, signals::exit_code};
+
+/// Passed as the first argument when ctr is started as the init process of a sandbox with its own PID namespace.
+pub const INIT_ARG: &str = "__sandbox_init";
+
+/// Signals the process started by ctr ignores in PID namespaces. The command is in the same
+/// process group and receives them itself, the process only passes on its exit code.
+const IGNORED_BY_INIT: [libc::c_int; 7] = [libc::SIGINT, libc::SIGQUIT, libc::SIGTERM, libc::SIGHUP, libc::SIGUSR1, libc::SIGUSR2, libc::SIGWINCH];
+
+/// `ST_RELATIME` from `<sys/statvfs.h>`, which the libc crate only defines for glibc.
+const ST_RELATIME: libc::c_ulong = 4096;
+/// The interface flag requests of `netdevice(7)`, typed for the `ioctl` of the current C library.
+const SIOCGIFFLAGS: libc::Ioctl = 0x8913;
+const SIOCSIFFLAGS: libc::Ioctl = 0x8914;
+
+/// Mount flags reported by `statvfs` that have to be kept when remounting read-only inside a user namespace.
+const LOCKED_FLAGS: [(libc::c_ulong, libc::c_ulong); 6] = [
+  (libc::ST_NOSUID, libc::MS_NOSUID),
+  (libc::ST_NODEV, libc::MS_NODEV),
+  (libc::ST_NOEXEC, libc::MS_NOEXEC),
+  (libc::ST_NOATIME, libc::MS_NOATIME),
+  (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+  (ST_RELATIME, libc::MS_RELATIME),
+];
+
+/// The sysctls that disable unprivileged user namespaces, and the value that does so.
+const USERNS_SYSCTLS: [(&str, &str); 3] = [
+  ("kernel.unprivileged_userns_clone", "0"),
+  ("user.max_user_namespaces", "0"),
+  ("kernel.apparmor_restrict_unprivileged_userns", "1"),
+];
+
+struct_gen! {
+  /// Isolates the command in unprivileged user and mount namespaces, see the `[sandbox]` section of presets.
+  /// Paths are resolved when the preset is loaded, so that applying the sandbox does not allocate.
+  pub struct Sandbox use Clone {
+    /// Whether the command keeps the network of the host, instead of a namespace with only loopback.
+    pub let network: bool = true;
+    pub let private_tmp: bool = false;
+    /// Whether the command runs in its own PID namespace, with ctr as its init process.
+    pub let private_pid: bool = false;
+    pub let read_only: Vec<CString> = Vec::new();
+    pub let writable: Vec<CString> = Vec::new();
+    /// Paths that are covered, directories by an empty tmpfs and files by `/dev/null`, along with whether they are directories.
+    pub let hide: Vec<(CString, bool)> = Vec::new();
+    /// The working directory of the command, entered again once the mounts are in place.
+    pub let working_dir: Option<CString> = None;
+  }
+
+  mod constructors {
+    /// Resolves the paths of `config`, relative ones against `base_dir`. Paths that do not exist are skipped.
+    pub fn resolve(config: &LaunchConfigSandbox, base_dir: &Path) -> Self {
+      let resolve = |field: &str, paths: &Option<Vec<String>>, warn: bool| {
+        paths.iter().flatten().filter_map(|path| {
+          let resolved = base_dir.join(path).canonicalize().inspect_err(|err| {
+            warn.then(|| CONSOLE.warn(format!("Skipping <brightmagenta>{path}</brightmagenta> in <brightblue>sandbox.{field}</brightblue>: {err}")));
+          }).ok()?;
+
+          CString::new(resolved.as_os_str().as_bytes()).ok()
+        }).collect::<Vec<CString>>()
+      };
+
+      Self {
+        network: config.network.unwrap_or(true),
+        private_tmp: config.private_tmp.unwrap_or(false),
+        private_pid: config.private_pid.unwrap_or(false),
+        read_only: resolve("read_only", &config.read_only, true),
+        writable: resolve("writable", &config.writable, true),
+        // there is nothing to hide if the path does not exist
+        hide: resolve("hide", &config.hide, false).into_iter()
+          .map(|path| {
+            let is_dir = Path::new(std::ffi::OsStr::from_bytes(path.as_bytes())).is_dir();
+            (path, is_dir)
+          })
+          .collect(),
+        working_dir: CString::new(base_dir.as_os_str().as_bytes()).ok(),
+      }
+    }
+  }
+
+  mod implementation {
+    /// Exits with an explanation if the sandbox cannot be created, most likely because
+    /// unprivileged user namespaces are disabled.
+    pub fn check_supported(self: &Self) {
+      let Err(err) = self.probe() else {
+        return;
+      };
+
+      let disabled_by = USERNS_SYSCTLS.iter().find(|(name, disabled)| {
+        let path = PathBuf::from("/proc/sys").join(name.replace('.', "/"));
+        std::fs::read_to_string(path).is_ok_and(|value| value.trim() == *disabled)
+      });
+
+      match disabled_by {
+        Some((name, disabled)) => CONSOLE.exit(format!(
+          "User namespaces are disabled on this system <brightblack>({name} = {disabled})</brightblack>, so the <brightblue>[sandbox]</brightblue> section cannot be applied"
+        )),
+        None => CONSOLE.exit(format!("Failed to create the namespaces for the <brightblue>[sandbox]</brightblue> section: {err}")),
+      }
+    }
+
+    fn namespaces(self: &Self) -> libc::c_int {
+      let mut flags = libc::CLONE_NEWUSER | libc::CLONE_NEWNS;
+      if !self.network {
+        flags |= libc::CLONE_NEWNET;
+      }
+
+      if self.private_pid {
+        flags |= libc::CLONE_NEWPID;
+      }
+
+      flags
+    }
+
+    /// Creates the namespaces in a throwaway child, to find out whether that is allowed at all.
+    fn probe(self: &Self) -> std::io::Result<()> {
+      // SAFETY: the child only calls `unshare` and `_exit`
+      match unsafe { libc::fork() } {
+        -1 => Err(std::io::Error::last_os_error()),
+        0 => unsafe {
+          let errno = if libc::unshare(self.namespaces()) == 0_i32 { 0_i32 } else { *libc::__errno_location() };
+          libc::_exit(errno)
+        },
+        child => {
+          let mut status: libc::c_int = 0;
+
+          // SAFETY: `status` is valid for writes
+          if unsafe { libc::waitpid(child, &mut status, 0) } == -1_i32 {
+            return Err(std::io::Error::last_os_error());
+          }
+
+          match libc::WEXITSTATUS(status) {
+            0 => Ok(()),
+            errno => Err(std::io::Error::from_raw_os_error(errno)),
+          }
+        },
+      }
+    }
+
+    /// Wraps `binary` in the init process of the sandbox if it gets its own PID namespace, see [`init`].
+    pub fn command(self: &Self, binary: &str) -> Command {
+      if !self.private_pid {
+        return Command::new(binary);
+      }
+
+      // resolves to ctr itself even if the sandbox hides its path
+      let mut command = Command::new("/proc/self/exe");
+      command.arg(INIT_ARG).arg(binary);
+      command
+    }
+
+    /// Short description of what the sandbox restricts, for `--dry-run`.
+    pub fn describe(self: &Self) -> String {
+      let list = |paths: &[CString]| paths.iter().map(|path| path.to_string_lossy().to_string()).collect::<Vec<String>>().join(", ");
+      let hidden = self.hide.iter().map(|(path, _)| path.clone()).collect::<Vec<CString>>();
+
+      let mut parts = vec![];
+      (!self.network).then(|| parts.push("no network".to_owned()));
+      self.private_tmp.then(|| parts.push("private /tmp".to_owned()));
+      self.private_pid.then(|| parts.push("private pids".to_owned()));
+      (!self.read_only.is_empty()).then(|| parts.push(format!("read-only {}", list(&self.read_only))));
+      (!self.writable.is_empty()).then(|| parts.push(format!("writable {}", list(&self.writable))));
+      (!hidden.is_empty()).then(|| parts.push(format!("hidden {}", list(&hidden))));
+
+      if parts.is_empty() {
+        "user and mount namespaces only".to_owned()
+      } else {
+        parts.join("; ")
+      }
+    }
+
+    /// Enters the namespaces and sets up the mounts in the calling process.
+    /// Runs between `fork` and `exec`, so it must not allocate.
+    pub fn apply(self: &Self) -> std::io::Result<()> {
+      macro_rules! check {
+        ($result:expr) => {
+          if ($result).is_negative() {
+            return Err(std::io::Error::last_os_error());
+          }
+        };
+      }
+
+      let null = std::ptr::null::<libc::c_char>();
+
+      // SAFETY: only async-signal-safe syscalls, on C strings that outlive them
+      unsafe {
+        let (uid, gid) = (libc::getuid(), libc::getgid());
+
+        // switching to the user of `run_as` makes the process non-dumpable, which hands `/proc/self` to root
+        check!(libc::prctl(libc::PR_SET_DUMPABLE, 1_u64));
+        check!(libc::unshare(self.namespaces()));
+
+        // the command keeps its own ids, every other id shows up as the overflow id
+        let mut buffer = [0_u8; 32];
+        write_file(c"/proc/self/setgroups", b"deny")?;
+        write_file(c"/proc/self/uid_map", id_map(uid, &mut buffer))?;
+        write_file(c"/proc/self/gid_map", id_map(gid, &mut buffer))?;
+
+        // changes to the mounts must not propagate back to the host
+        check!(libc::mount(null, c"/".as_ptr(), null, libc::MS_REC | libc::MS_PRIVATE, std::ptr::null()));
+
+        // mounted first, so that they are carried over as they are when a parent is made read-only
+        for path in &self.writable {
+          check!(libc::mount(path.as_ptr(), path.as_ptr(), null, libc::MS_BIND | libc::MS_REC, std::ptr::null()));
+        }
+
+        for path in &self.read_only {
+          check!(libc::mount(path.as_ptr(), path.as_ptr(), null, libc::MS_BIND | libc::MS_REC, std::ptr::null()));
+
+          let mut stats: libc::statvfs = std::mem::zeroed();
+          check!(libc::statvfs(path.as_ptr(), &mut stats));
+          let locked = LOCKED_FLAGS.iter().filter(|(stat, _)| stats.f_flag & stat != 0).fold(0, |flags, (_, mount)| flags | mount);
+
+          check!(libc::mount(null, path.as_ptr(), null, libc::MS_REMOUNT | libc::MS_BIND | libc::MS_RDONLY | locked, std::ptr::null()));
+        }
+
+        for (path, is_dir) in &self.hide {
+          if *is_dir {
+            check!(libc::mount(c"tmpfs".as_ptr(), path.as_ptr(), c"tmpfs".as_ptr(), libc::MS_NOSUID | libc::MS_NODEV, c"mode=0755".as_ptr().cast()));
+          } else {
+            check!(libc::mount(c"/dev/null".as_ptr(), path.as_ptr(), null, libc::MS_BIND, std::ptr::null()));
+          }
+        }
+
+        // the old working directory still points below the mounts that now cover it.
+        // Entered before /tmp is replaced, so that commands run from there keep their directory
+        if let Some(ref working_dir) = self.working_dir {
+          check!(libc::chdir(working_dir.as_ptr()));
+        }
+
+        if self.private_tmp {
+          check!(libc::mount(c"tmpfs".as_ptr(), c"/tmp".as_ptr(), c"tmpfs".as_ptr(), libc::MS_NOSUID | libc::MS_NODEV, c"mode=1777".as_ptr().cast()));
+        }
+
+        if !self.network {
+          bring_up_loopback()?;
+        }
+      }
+
+      Ok(())
+    }
+  }
+}
+
+/// Writes `contents` to the file at `path`. Does not allocate.
+fn write_file(path: &CStr, contents: &[u8]) -> std::io::Result<()> {
+  // SAFETY: `path` is a valid C string, and `contents` is valid for reads of its length
+  unsafe {
+    let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+    if fd < 0_i32 {
+      return Err(std::io::Error::last_os_error());
+    }
+
+    let written = libc::write(fd, contents.as_ptr().cast(), contents.len());
+    libc::close(fd);
+
+    if written < 0_isize {
+      return Err(std::io::Error::last_os_error());
+    }
+  }
+
+  Ok(())
+}
+
+/// Formats the line `<id> <id> 1` for `uid_map` and `gid_map` into `buffer`. Does not allocate.
+fn id_map(id: u32, buffer: &mut [u8; 32]) -> &[u8] {
+  let mut digits = [0_u8; 10];
+  let mut count = 0_usize;
+  let mut rest = id;
+
+  loop {
+    if let Some(digit) = digits.get_mut(count) {
+      *digit = b'0'.saturating_add(u8::try_from(rest % 10).unwrap_or(0));
+    }
+
+    count = count.saturating_add(1);
+    rest /= 10;
+    if rest == 0 {
+      break;
+    }
+  }
+
+  let number = digits.get(..count).unwrap_or_default();
+  let mut length = 0_usize;
+  for byte in number.iter().rev().chain(b" ").chain(number.iter().rev()).chain(b" 1") {
+    if let Some(slot) = buffer.get_mut(length) {
+      *slot = *byte;
+    }
+
+    length = length.saturating_add(1);
+  }
+
+  buffer.get(..length).unwrap_or_default()
+}
+
+/// Brings up the loopback interface of a new network namespace, which starts out down. Does not allocate.
+fn bring_up_loopback() -> std::io::Result<()> {
+  // SAFETY: `request` is plain data that is valid for reads and writes
+  unsafe {
+    let socket = libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0);
+    if socket < 0_i32 {
+      return Err(std::io::Error::last_os_error());
+    }
+
+    let mut request: libc::ifreq = std::mem::zeroed();
+    for (slot, byte) in request.ifr_name.iter_mut().zip(b"lo") {
+      *slot = libc::c_char::try_from(*byte).unwrap_or_default();
+    }
+
+    let up = libc::c_short::try_from(libc::IFF_UP).unwrap_or_default();
+    let result = if libc::ioctl(socket, SIOCGIFFLAGS, &mut request) < 0_i32 {
+      -1_i32
+    } else {
+      request.ifr_ifru.ifru_flags |= up;
+      libc::ioctl(socket, SIOCSIFFLAGS, &request)
+    };
+
+    let error = std::io::Error::last_os_error();
+    libc::close(socket);
+
+    if result < 0_i32 {
+      return Err(error);
+    }
+  }
+
+  Ok(())
+}
+
+/// Runs `args` in the PID namespace of the sandbox, see [`Sandbox::command`].
+/// The process ctr started stays outside the namespace and forks the init of the namespace,
+/// which in turn starts the command and reaps the processes that are left behind.
+pub fn init(args: &[String]) -> ! {
+  let Some((program, arguments)) = args.split_first() else {
+    CONSOLE.exit("No binary specified")
+  };
+
+  // SAFETY: ctr was just started and has no other threads
+  match unsafe { libc::fork() } {
+    -1 => CONSOLE.exit(format!("Failed to start the sandbox: {}", std::io::Error::last_os_error())),
+    0 => run_init(program, arguments),
+    child => {
+      for signal in IGNORED_BY_INIT {
+        // SAFETY: ignoring a signal has no memory safety requirements
+        unsafe {
+          libc::signal(signal, libc::SIG_IGN);
+        }
+      }
+
+      std::process::exit(wait_for(child).map_or(1_i32, exit_code));
+    },
+  }
+}
+
+/// The init process of the namespace, whose exit takes down everything left in it.
+/// As init, it is not affected by signals it has no handler for, so only the command receives them.
+fn run_init(program: &str, arguments: &[String]) -> ! {
+  // a /proc of the new namespace, so that the command only sees its own processes.
+  // The kernel refuses this while parts of the /proc of the host are covered, in which case that one stays
+  // SAFETY: all arguments are valid C strings
+  unsafe {
+    libc::mount(c"proc".as_ptr(), c"/proc".as_ptr(), c"proc".as_ptr(), libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC, std::ptr::null());
+  }
+
+  // reaped below along with everything else that ends up in the namespace
+  let pid = Command::new(program).args(arguments).spawn()
+    .map(|child| libc::pid_t::try_from(child.id()).unwrap_or(0_i32))
+    .unwrap_or_else(|err| CONSOLE.exit(format!("Failed to run `{program}`: {err}")));
+
+  loop {
+    let mut status: libc::c_int = 0;
+
+    // SAFETY: `status` is valid for writes
+    match unsafe { libc::waitpid(-1_i32, &mut status, 0) } {
+      -1 if std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted => continue,
+      -1 => std::process::exit(1),
+      reaped if reaped == pid => std::process::exit(exit_code(ExitStatus::from_raw(status))),
+      _ => continue,
+    }
+  }
+}
+
+fn wait_for(pid: libc::pid_t) -> std::io::Result<ExitStatus> {
+  let mut status: libc::c_int = 0;
+
+  loop {
+    // SAFETY: `status` is valid for writes
+    if unsafe { libc::waitpid(pid, &mut status, 0) } != -1_i32 {
+      return Ok(ExitStatus::from_raw(status));
+    }
+
+    let err = std::io::Error::last_os_error();
+    if err.kind() != std::io::ErrorKind::Interrupted {
+      return Err(err);
+    }
+  }
+}
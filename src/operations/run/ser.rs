@@ -1,32 +1,589 @@
-use std::{collections::HashMap, sync::LazyLock};
+use std::{
+  collections::HashMap,
+  fmt::{Display, Formatter},
+  hash::BuildHasher,
+  path::{Path, PathBuf},
+  process::ExitStatus,
+  sync::LazyLock,
+  time::Duration,
+};
 
-use serde::Deserialize;
-use std_v2::{env::consts::HOME, struct_gen, toml::Value};
+use serde::{Deserialize, Serialize};
+use std_v2::{
+  console::CONSOLE,
+  env::consts::CTR_CONFIG_DIR,
+  struct_gen,
+  toml::{de, Value},
+};
+
+use super::{
+  dotenv,
+  duration::HumanDuration,
+  elevate::Elevate,
+  identity::Identity,
+  interpolate::{interpolate, lookup, Context},
+  limits::{CpuList, IoPriorityClass, LimitValue, ProcessLimits, Umask},
+  log::OutputLog,
+  sandbox::Sandbox,
+  signals::Signal,
+};
 type EnvironmentMap = HashMap<String, Value>;
-static USER_STR: LazyLock<String> = LazyLock::new(|| HOME.to_string_lossy().to_string());
+pub type ParamMap = HashMap<String, LaunchConfigParam>;
+
+/// `general.command` is either a script for the shell or an argv that is executed directly.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum LaunchCommand {
+  Shell(String),
+  Exec(Vec<String>),
+}
+
+impl Default for LaunchCommand {
+  fn default() -> Self {
+    Self::Shell(String::new())
+  }
+}
+
+impl Display for LaunchCommand {
+  fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+    match self {
+      Self::Shell(script) => write!(f, "{script}"),
+      Self::Exec(argv) => write!(f, "{}", argv.join(" ")),
+    }
+  }
+}
+
+/// When a command is started again after it exited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+  #[default]
+  No,
+  OnFailure,
+  Always,
+}
+
+/// Which results of `ctr run` raise a desktop notification.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyOn {
+  #[default]
+  Always,
+  Failure,
+  Success,
+}
+
+/// What happens to a running command when a watched file changes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchAction {
+  /// Stop the command and start it again right away.
+  #[default]
+  Restart,
+  /// Let the command finish, then start it again.
+  Queue,
+}
+
+/// What happens when a hook exits with a non-zero status.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HookPolicy {
+  #[default]
+  Abort,
+  Warn,
+  Ignore,
+}
+
+/// A hook is either a plain command, or a table with a command and its failure policy.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum LaunchHook {
+  Command(LaunchCommand),
+  Detailed {
+    command: LaunchCommand,
+    policy: Option<HookPolicy>,
+  },
+}
+
+impl LaunchHook {
+  pub const fn command(&self) -> &LaunchCommand {
+    match self {
+      Self::Command(command) | Self::Detailed { command, .. } => command,
+    }
+  }
+
+  pub fn policy(&self) -> HookPolicy {
+    match self {
+      Self::Command(_) => HookPolicy::default(),
+      Self::Detailed { policy, .. } => policy.unwrap_or_default(),
+    }
+  }
+}
+/// An entry of `env_files`, either a path or `{ path = "...", optional = true }`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum EnvFile {
+  Path(String),
+  Detailed {
+    path: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    optional: bool,
+  },
+}
+
+impl EnvFile {
+  pub fn path(&self) -> &str {
+    match self {
+      Self::Path(path) | Self::Detailed { path, .. } => path,
+    }
+  }
+
+  pub const fn optional(&self) -> bool {
+    match self {
+      Self::Path(_) => false,
+      Self::Detailed { optional, .. } => *optional,
+    }
+  }
+}
+
+/// `ready.tcp` is either a port on localhost or a `host:port` address.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum TcpTarget {
+  Port(u16),
+  Address(String),
+}
+
+impl Display for TcpTarget {
+  fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+    match self {
+      Self::Port(port) => write!(f, "localhost:{port}"),
+      Self::Address(address) => write!(f, "{address}"),
+    }
+  }
+}
+
 struct_gen! {
-  pub struct LaunchConfigRunAs use Deserialize, Clone {
+  pub struct LaunchConfigRunAs use Deserialize, Serialize, Clone {
+    /// Same as setting `elevate` to the default from `config.toml`.
     pub let sudo: Option<bool> = Some(false);
+    /// The tool that switches to the user, instead of ctr itself. See [`GlobalConfig`] for the default of `sudo = true`.
+    pub let elevate: Option<Elevate> = None;
     pub let user: Option<String> = None;
     pub let group: Option<String> = None;
+    /// Runs the command through the login shell of the user, so that its profile is loaded.
+    pub let login_shell: Option<bool> = None;
+  }
+
+  mod implementation {
+    /// Whether the command runs through `sudo` or another tool instead of ctr switching to the user.
+    pub fn elevated(self: &Self) -> bool {
+      self.elevate.is_some() || self.sudo.unwrap_or(false)
+    }
+  }
+}
+
+struct_gen! {
+  /// Settings for every preset, read from `config.toml` in the config directory of ctr.
+  pub struct GlobalConfig use Deserialize, Serialize, Clone {
+    pub let run_as: Option<GlobalConfigRunAs> = None;
+  }
+
+  mod constructors {
+    pub fn path() -> PathBuf {
+      CTR_CONFIG_DIR.join("config.toml")
+    }
+
+    pub fn load() -> Self {
+      let path = Self::path();
+      if !path.is_file() {
+        return Self::default();
+      }
+
+      std_v2::toml::parse_file(&path).unwrap_or_else(|err| CONSOLE.exit(format!("{}: {err}", path.display())))
+    }
   }
 }
 
 struct_gen! {
-  pub struct LaunchConfigGeneral use Deserialize {
+  pub struct GlobalConfigRunAs use Deserialize, Serialize, Clone {
+    /// The tool used by presets that set `run_as.sudo = true`, or `run_as.elevate = "auto"` when unset.
+    pub let elevate: Option<Elevate> = None;
+  }
+}
+
+struct_gen! {
+  pub struct LaunchConfigHooks use Deserialize, Serialize, Clone {
+    pub let before: Option<Vec<LaunchHook>> = None;
+    pub let after: Option<Vec<LaunchHook>> = None;
+    pub let on_success: Option<Vec<LaunchHook>> = None;
+    pub let on_failure: Option<Vec<LaunchHook>> = None;
+  }
+}
+
+struct_gen! {
+  pub struct LaunchConfigLimits use Deserialize, Serialize, Clone {
+    pub let nofile: Option<LimitValue> = None;
+    pub let nproc: Option<LimitValue> = None;
+    #[serde(rename = "as")]
+    pub let address_space: Option<LimitValue> = None;
+    pub let cpu: Option<LimitValue> = None;
+    pub let core: Option<LimitValue> = None;
+    /// Sets the hard limits to the same values, instead of only the soft limits.
+    pub let hard: Option<bool> = None;
+  }
+}
+
+struct_gen! {
+  pub struct LaunchConfigScheduling use Deserialize, Serialize, Clone {
+    pub let nice: Option<i32> = None;
+    pub let ioprio_class: Option<IoPriorityClass> = None;
+    pub let ioprio_level: Option<u32> = None;
+    pub let cpu_affinity: Option<CpuList> = None;
+  }
+}
+
+struct_gen! {
+  pub struct LaunchConfigLog use Deserialize, Serialize, Clone {
+    pub let path: Option<String> = None;
+    pub let append: Option<bool> = None;
+    pub let max_size: Option<LimitValue> = None;
+    pub let keep: Option<u32> = None;
+    pub let timestamps: Option<bool> = None;
+    pub let prefix: Option<bool> = None;
+  }
+}
+
+struct_gen! {
+  /// Probes that decide when a preset started as a dependency is ready. All configured probes have to pass.
+  pub struct LaunchConfigReady use Deserialize, Serialize, Clone {
+    pub let tcp: Option<TcpTarget> = None;
+    pub let http: Option<String> = None;
+    pub let file: Option<String> = None;
+    pub let log: Option<String> = None;
+    pub let command: Option<String> = None;
+    pub let timeout: Option<HumanDuration> = None;
+    pub let interval: Option<HumanDuration> = None;
+  }
+}
+
+struct_gen! {
+  pub struct LaunchConfigGroup use Deserialize, Serialize, Clone {
+    pub let members: Option<Vec<String>> = None;
+    pub let fail_fast: Option<bool> = None;
+  }
+}
+
+struct_gen! {
+  pub struct LaunchConfigNotify use Deserialize, Serialize, Clone {
+    pub let on: Option<NotifyOn> = None;
+    pub let min_duration: Option<HumanDuration> = None;
+  }
+}
+
+struct_gen! {
+  pub struct LaunchConfigWatch use Deserialize, Serialize, Clone {
+    pub let paths: Option<Vec<String>> = None;
+    pub let ignore: Option<Vec<String>> = None;
+    pub let debounce: Option<HumanDuration> = None;
+    pub let on_change: Option<WatchAction> = None;
+  }
+}
+
+struct_gen! {
+  /// Namespaces that isolate the command from the host, see [`Sandbox`].
+  pub struct LaunchConfigSandbox use Deserialize, Serialize, Clone {
+    pub let network: Option<bool> = None;
+    pub let private_tmp: Option<bool> = None;
+    pub let private_pid: Option<bool> = None;
+    pub let read_only: Option<Vec<String>> = None;
+    pub let writable: Option<Vec<String>> = None;
+    pub let hide: Option<Vec<String>> = None;
+  }
+}
+
+struct_gen! {
+  pub struct LaunchConfigParam use Deserialize, Serialize, Clone {
+    pub let default: Option<String> = None;
+    pub let required: Option<bool> = None;
+    pub let choices: Option<Vec<String>> = None;
+    pub let description: Option<String> = None;
+  }
+}
+
+struct_gen! {
+  pub struct LaunchConfigGeneral use Deserialize, Serialize {
     pub let preserve_env: Option<bool> = Some(true);
     pub let deamonize: Option<bool> = Some(false);
-    pub let working_dir: Option<String> = Some(USER_STR.to_owned());
-    pub let command: Option<String> = None;
+    pub let working_dir: Option<String> = None;
+    pub let command: Option<LaunchCommand> = None;
     pub let shell: Option<String> = None;
+    pub let restart: Option<RestartPolicy> = None;
+    pub let max_restarts: Option<u32> = None;
+    pub let restart_delay: Option<HumanDuration> = None;
+    pub let restart_max_delay: Option<HumanDuration> = None;
+    pub let timeout: Option<HumanDuration> = None;
+    pub let timeout_grace: Option<HumanDuration> = None;
+    pub let stop_signal: Option<Signal> = None;
+    pub let stop_grace: Option<HumanDuration> = None;
+    pub let tty: Option<bool> = None;
+    pub let umask: Option<Umask> = None;
+  }
+
+  mod constructors {
+    /// A section where nothing is set, so that merging it does not override anything.
+    pub fn unset() -> Self {
+      Self {
+        preserve_env: None,
+        deamonize: None,
+        working_dir: None,
+        command: None,
+        shell: None,
+        restart: None,
+        max_restarts: None,
+        restart_delay: None,
+        restart_max_delay: None,
+        timeout: None,
+        timeout_grace: None,
+        stop_signal: None,
+        stop_grace: None,
+        tty: None,
+        umask: None,
+      }
+    }
   }
 }
 
 struct_gen! {
-  pub struct LaunchConfig use Deserialize {
+  pub struct LaunchConfig use Deserialize, Serialize {
+    pub let extends: Option<Vec<String>> = None;
+    pub let unset_environment: Option<Vec<String>> = None;
+    pub let env_files: Option<Vec<EnvFile>> = None;
+    pub let depends_on: Option<Vec<String>> = None;
+    #[serde(default = "LaunchConfigGeneral::unset")]
     pub let general: LaunchConfigGeneral = LaunchConfigGeneral::default();
     pub let run_as: Option<LaunchConfigRunAs> = None;
     pub let environment: Option<EnvironmentMap> = Some(EnvironmentMap::new());
+    pub let params: Option<ParamMap> = None;
+    pub let hooks: Option<LaunchConfigHooks> = None;
+    pub let limits: Option<LaunchConfigLimits> = None;
+    pub let scheduling: Option<LaunchConfigScheduling> = None;
+    pub let log: Option<LaunchConfigLog> = None;
+    pub let group: Option<LaunchConfigGroup> = None;
+    pub let ready: Option<LaunchConfigReady> = None;
+    pub let notify: Option<LaunchConfigNotify> = None;
+    pub let watch: Option<LaunchConfigWatch> = None;
+    pub let sandbox: Option<LaunchConfigSandbox> = None;
+  }
+
+  mod constructors {
+    pub fn from_file(path: &Path) -> Result<Self, de::Error> {
+      std_v2::toml::parse_file(path)
+    }
+
+    /// Loads the preset at `path`, including everything it extends, on top of the built-in defaults.
+    pub fn resolve(path: &Path) -> Self {
+      let mut resolved = Self::default();
+      resolved.merge(Self::from_chain(path, &mut vec![path.to_path_buf()]));
+      if let Err(err) = resolved.interpolate(path) {
+        CONSOLE.exit(err);
+      }
+
+      resolved.general.working_dir = resolved.general.working_dir.map(|e| {
+        match Path::new(e.as_str()).canonicalize() {
+          Ok(path) => path.to_string_lossy().to_string(),
+          _ => e
+        }
+      });
+
+      resolved
+    }
+  }
+
+  mod provenance {
+    /// The files of the inheritance chain of `path`, in the order they are merged.
+    /// Cycles and missing presets have already been reported by [`Self::resolve`].
+    fn chain_files(path: &Path) -> Vec<PathBuf> {
+      let mut files = vec![];
+      let extends = Self::from_file(path).ok().and_then(|config| config.extends).unwrap_or_default();
+
+      for parent in extends {
+        let parent_path = super::parent_preset_path(path, &parent);
+        if !files.contains(&parent_path) && parent_path != path {
+          files.extend(Self::chain_files(&parent_path));
+        }
+      }
+
+      files.push(path.to_path_buf());
+      files
+    }
+
+    /// Maps every field to what set it last: `default` or the preset file.
+    pub fn sources(path: &Path) -> HashMap<String, String> {
+      let mut sources = HashMap::new();
+      for (key, _) in Self::flatten(&Value::try_from(Self::default()).unwrap_or(Value::Table(Default::default()))) {
+        sources.insert(key, "default".to_owned());
+      }
+
+      for file in Self::chain_files(path) {
+        if let Ok(value) = std_v2::toml::parse_file::<Value>(&file) {
+          for (key, _) in Self::flatten(&value) {
+            sources.insert(key, file.display().to_string());
+          }
+        }
+      }
+
+      sources
+    }
+
+    /// Flattens nested tables into dotted keys, like `general.command` or `environment.PATH`.
+    pub fn flatten(value: &Value) -> Vec<(String, String)> {
+      let mut fields = vec![];
+      Self::flatten_into(value, "", &mut fields);
+      fields
+    }
+
+    fn flatten_into(value: &Value, prefix: &str, fields: &mut Vec<(String, String)>) {
+      match value {
+        Value::Table(table) => {
+          for (key, nested) in table {
+            let path = if prefix.is_empty() { key.to_owned() } else { format!("{prefix}.{key}") };
+            Self::flatten_into(nested, &path, fields);
+          }
+        },
+        Value::String(string) => fields.push((prefix.to_owned(), string.to_owned())),
+        other => fields.push((prefix.to_owned(), other.to_string())),
+      }
+    }
+  }
+
+  mod interpolation {
+    /// Expands variables and `~` in the string fields, see [`interpolate`].
+    /// The environment of the preset is expanded first, so that the other fields can use it.
+    fn interpolate(self: &mut Self, path: &Path) -> Result<(), String> {
+      let preset = path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().to_string());
+      let builtin = |name: &str| lookup(name, &preset);
+      let expand_with = |vars: &dyn Fn(&str) -> Option<String>, field: &str, value: &str, context: Context| {
+        interpolate(value, vars, context).map_err(|err| format!("Failed to expand <brightblue>{field}</brightblue> in <bold>{}</bold>: {err}", path.display()))
+      };
+
+      let general = &mut self.general;
+      general.working_dir = general.working_dir.as_ref().map(|dir| expand_with(&builtin, "general.working_dir", dir, Context::Value)).transpose()?;
+      general.shell = general.shell.as_ref().map(|shell| expand_with(&builtin, "general.shell", shell, Context::Value)).transpose()?;
+
+      if let Some(ref mut run_as) = self.run_as {
+        run_as.user = run_as.user.as_ref().map(|user| expand_with(&builtin, "run_as.user", user, Context::Value)).transpose()?;
+        run_as.group = run_as.group.as_ref().map(|group| expand_with(&builtin, "run_as.group", group, Context::Value)).transpose()?;
+      }
+
+      for file in self.env_files.iter_mut().flatten() {
+        let expanded = expand_with(&builtin, "env_files", file.path(), Context::Value)?;
+        match file {
+          EnvFile::Path(path) | EnvFile::Detailed { path, .. } => *path = expanded,
+        }
+      }
+
+      for (key, value) in self.environment.iter_mut().flatten() {
+        let field = format!("environment.{key}");
+        match value {
+          Value::String(string) => *string = expand_with(&builtin, &field, string, Context::Value)?,
+          Value::Array(values) => {
+            for element in values.iter_mut() {
+              if let Value::String(string) = element {
+                *string = expand_with(&builtin, &field, string, Context::Value)?;
+              }
+            }
+          },
+          _ => {},
+        }
+      }
+
+      // `[environment]` wins over the env files, both win over the built-in variables and the environment of ctr
+      let preserve_env = self.general.preserve_env.unwrap_or(true);
+      let mut preset_vars = LaunchOptions::load_env_files(self.env_files.as_deref().unwrap_or_default(), self.general.working_dir.as_deref(), preserve_env);
+      preset_vars.extend(self.environment.iter().flatten().map(|(key, value)| (key.to_owned(), LaunchOptions::render_value(key, value))));
+
+      let vars = |name: &str| preset_vars.iter().rev().find(|(key, _)| key == name).map(|(_, value)| value.to_owned()).or_else(|| builtin(name));
+      let expand = |field: &str, value: &str, context: Context| expand_with(&vars, field, value, context);
+
+      // no shell runs an argv, so its arguments are expanded like any other value
+      self.general.command = self.general.command.as_ref().map(|command| match command {
+        LaunchCommand::Shell(script) => expand("general.command", script, Context::Command).map(LaunchCommand::Shell),
+        LaunchCommand::Exec(argv) => argv.iter().map(|arg| expand("general.command", arg, Context::Value)).collect::<Result<_, _>>().map(LaunchCommand::Exec),
+      }).transpose()?;
+
+      if let Some(ref mut log) = self.log {
+        log.path = log.path.as_ref().map(|log_path| expand("log.path", log_path, Context::Value)).transpose()?;
+      }
+
+      if let Some(ref mut ready) = self.ready {
+        ready.http = ready.http.as_ref().map(|url| expand("ready.http", url, Context::Value)).transpose()?;
+        ready.file = ready.file.as_ref().map(|file| expand("ready.file", file, Context::Value)).transpose()?;
+        ready.command = ready.command.as_ref().map(|command| expand("ready.command", command, Context::Command)).transpose()?;
+      }
+
+      if let Some(ref mut watch) = self.watch {
+        for watch_path in watch.paths.iter_mut().flatten() {
+          *watch_path = expand("watch.paths", watch_path, Context::Value)?;
+        }
+      }
+
+      if let Some(ref mut sandbox) = self.sandbox {
+        for (field, paths) in [("sandbox.read_only", &mut sandbox.read_only), ("sandbox.writable", &mut sandbox.writable), ("sandbox.hide", &mut sandbox.hide)] {
+          for sandbox_path in paths.iter_mut().flatten() {
+            *sandbox_path = expand(field, sandbox_path, Context::Value)?;
+          }
+        }
+      }
+
+      Ok(())
+    }
+  }
+
+  mod inheritance {
+    /// Merges the presets listed in `extends` in declaration order, followed by the preset itself.
+    /// `chain` holds the presets that are currently being resolved and is used to detect cycles.
+    fn from_chain(path: &Path, chain: &mut Vec<PathBuf>) -> Self {
+      let config = Self::from_file(path).unwrap_or_else(|err| CONSOLE.exit(format!("{}: {err}", path.display())));
+      let mut resolved: Option<Self> = None;
+
+      for parent in config.extends.clone().unwrap_or_default() {
+        let parent_path = super::parent_preset_path(path, &parent);
+
+        if chain.contains(&parent_path) {
+          chain.push(parent_path);
+          CONSOLE.exit(format!("Presets cannot extend each other in a cycle: {}", Self::chain_names(chain)));
+        }
+
+        if !parent_path.exists() {
+          CONSOLE.exit(format!("The preset <brightmagenta>{parent}</brightmagenta> extended by {} does not exist", path.display()));
+        }
+
+        chain.push(parent_path.clone());
+        let parent_config = Self::from_chain(&parent_path, chain);
+        chain.pop();
+
+        match resolved {
+          Some(ref mut merged) => merged.merge(parent_config),
+          None => resolved = Some(parent_config),
+        }
+      }
+
+      match resolved {
+        Some(mut merged) => {
+          merged.merge(config);
+          merged
+        },
+        None => config,
+      }
+    }
+
+    fn chain_names(chain: &[PathBuf]) -> String {
+      chain.iter()
+        .map(|path| path.file_stem().map_or(path.display().to_string(), |stem| stem.to_string_lossy().to_string()))
+        .collect::<Vec<String>>()
+        .join(" -> ")
+    }
   }
 
   mod implementation {
@@ -52,12 +609,43 @@ struct_gen! {
         };
       }
 
-      merge!(general { preserve_env, deamonize, working_dir, command, shell });
-      merge!(Option<run_as> { user, group, sudo });
+      merge!(general { preserve_env, deamonize, working_dir, command, shell, restart, max_restarts, restart_delay, restart_max_delay, timeout, timeout_grace, stop_signal, stop_grace, tty, umask });
+      merge!(Option<run_as> { user, group, sudo, elevate, login_shell });
+      merge!(Option<hooks> { before, after, on_success, on_failure });
+      merge!(Option<limits> { nofile, nproc, address_space, cpu, core, hard });
+      merge!(Option<scheduling> { nice, ioprio_class, ioprio_level, cpu_affinity });
+      merge!(Option<log> { path, append, max_size, keep, timestamps, prefix });
+      merge!(Option<group> { members, fail_fast });
+      merge!(Option<ready> { tcp, http, file, log, command, timeout, interval });
+      merge!(Option<notify> { on, min_duration });
+      merge!(Option<watch> { paths, ignore, debounce, on_change });
+      merge!(Option<sandbox> { network, private_tmp, private_pid, read_only, writable, hide });
+
+      if other.depends_on.is_some() {
+        self.depends_on = other.depends_on;
+      }
+
+      // inherited variables can be dropped again with `unset_environment`
+      if let Some(unset) = other.unset_environment {
+        if let Some(ref mut environment) = self.environment {
+          environment.retain(|key, _| !unset.contains(key));
+        }
+
+        self.unset_environment.get_or_insert_with(Vec::new).extend(unset);
+      }
+
+      // files of the parents are loaded first, so the preset itself can override them
+      if let Some(files) = other.env_files {
+        self.env_files.get_or_insert_with(Vec::new).extend(files);
+      }
 
       if let Some(env) = other.environment {
         self.environment.get_or_insert_with(EnvironmentMap::new).extend(env);
       }
+
+      if let Some(params) = other.params {
+        self.params.get_or_insert_with(ParamMap::new).extend(params);
+      }
     }
   }
 }
@@ -67,24 +655,254 @@ struct_gen! {
   pub struct LaunchOptions {
     pub let preserve_env: bool = true;
     pub let environment: EnvironmentMap = EnvironmentMap::new();
-    pub let current_dir: String = USER_STR.to_owned();
+    pub let env_file_vars: Vec<(String, String)> = Vec::new();
+    pub let unset_environment: Vec<String> = Vec::new();
+    pub let current_dir: Option<String> = None;
     pub let daemonize: bool = false;
-    pub let command: String = String::new();
+    pub let command: LaunchCommand = LaunchCommand::default();
     pub let run_as: Option<LaunchConfigRunAs> = None;
+    /// The user the command is switched to, if `run_as` is used without `elevate`.
+    pub let identity: Option<Identity> = None;
+    /// The tool the command runs through, if `run_as` is used with `elevate` or `sudo`. Never `auto`.
+    pub let elevate: Option<Elevate> = None;
     pub let shell: String = SHELL.to_owned();
+    pub let hooks: LaunchConfigHooks = LaunchConfigHooks::default();
+    pub let restart: RestartPolicy = RestartPolicy::No;
+    pub let max_restarts: Option<u32> = None;
+    pub let restart_delay: Duration = Duration::from_secs(1);
+    pub let restart_max_delay: Duration = Duration::from_secs(30);
+    pub let timeout: Option<Duration> = None;
+    pub let timeout_grace: Duration = Duration::from_secs(10);
+    /// Sent to the process group of the command when ctr stops it, followed by SIGKILL after `stop_grace`.
+    pub let stop_signal: Signal = Signal::default();
+    pub let stop_grace: Duration = Duration::from_secs(10);
+    /// Whether the command runs under a pseudo-terminal, see `pty::Pty`.
+    pub let tty: bool = false;
+    pub let limits: ProcessLimits = ProcessLimits::default();
+    pub let log: Option<OutputLog> = None;
+    pub let notify: Option<LaunchConfigNotify> = None;
+    pub let watch: Option<LaunchConfigWatch> = None;
+    pub let sandbox: Option<Sandbox> = None;
   }
 
   impl From<LaunchConfig> {
     fn from(config: LaunchConfig) -> Self {
+      let sandbox = config.sandbox.as_ref().map(|sandbox| {
+        let base_dir = config.general.working_dir.as_ref().map_or_else(|| std::env::current_dir().unwrap_or_default(), PathBuf::from);
+        Sandbox::resolve(sandbox, &base_dir)
+      });
+
+      let identity = config.run_as.as_ref().filter(|run_as| !run_as.elevated()).map(|run_as| {
+        Identity::resolve(&run_as.user, &run_as.group, run_as.login_shell.unwrap_or(false))
+          .unwrap_or_else(|err| CONSOLE.exit(format!("Invalid <brightblue>[run_as]</brightblue> section, {err}")))
+      });
+
+      // `elevate` of the preset wins over the default, which only replaces what `sudo = true` runs through
+      let elevate = config.run_as.as_ref().filter(|run_as| run_as.elevated()).map(|run_as| {
+        run_as.elevate
+          .or_else(|| GlobalConfig::load().run_as.and_then(|global| global.elevate))
+          .unwrap_or_default()
+          .detect()
+          .unwrap_or_else(|err| CONSOLE.exit(format!("Invalid <brightblue>[run_as]</brightblue> section, {err}")))
+      });
+
       Self {
         run_as: config.run_as,
+        identity,
+        elevate,
         preserve_env: config.general.preserve_env.unwrap_or(true),
+        env_file_vars: Self::load_env_files(&config.env_files.unwrap_or_default(), config.general.working_dir.as_deref(), config.general.preserve_env.unwrap_or(true)),
         environment: config.environment.unwrap_or_default(),
-        current_dir: config.general.working_dir.unwrap_or(USER_STR.to_owned()),
+        unset_environment: config.unset_environment.unwrap_or_default(),
+        current_dir: config.general.working_dir,
         command: config.general.command.unwrap_or_default(),
         daemonize: config.general.deamonize.unwrap_or(false),
         shell: config.general.shell.unwrap_or(SHELL.to_owned()),
+        hooks: config.hooks.unwrap_or_default(),
+        restart: config.general.restart.unwrap_or_default(),
+        max_restarts: config.general.max_restarts,
+        restart_delay: config.general.restart_delay.map_or(Duration::from_secs(1), |delay| delay.0),
+        restart_max_delay: config.general.restart_max_delay.map_or(Duration::from_secs(30), |delay| delay.0),
+        timeout: config.general.timeout.map(|timeout| timeout.0),
+        timeout_grace: config.general.timeout_grace.or(config.general.stop_grace).map_or(Duration::from_secs(10), |grace| grace.0),
+        stop_signal: config.general.stop_signal.unwrap_or_default(),
+        stop_grace: config.general.stop_grace.map_or(Duration::from_secs(10), |grace| grace.0),
+        tty: config.general.tty.unwrap_or(false),
+        limits: Self::process_limits(config.general.umask, config.limits.unwrap_or_default(), config.scheduling.unwrap_or_default()),
+        log: config.log.and_then(Self::output_log),
+        notify: config.notify,
+        watch: config.watch,
+        sandbox,
+      }
+    }
+  }
+
+  mod constructors {
+    /// Loads `files` in order. Relative paths are resolved against the working directory,
+    /// and `${VAR}` is expanded against the variables loaded so far, then the environment of ctr if it is preserved.
+    fn load_env_files(files: &[EnvFile], working_dir: Option<&str>, preserve_env: bool) -> Vec<(String, String)> {
+      let base_dir = working_dir.map_or_else(|| std::env::current_dir().unwrap_or_default(), PathBuf::from);
+      let mut vars: Vec<(String, String)> = vec![];
+
+      for file in files {
+        let path = base_dir.join(file.path());
+
+        let contents = match std::fs::read_to_string(&path) {
+          Ok(contents) => contents,
+          Err(err) if err.kind() == std::io::ErrorKind::NotFound && file.optional() => continue,
+          Err(err) => CONSOLE.exit(format!("Failed to read the env file <brightmagenta>{}</brightmagenta>: {err}", path.display())),
+        };
+
+        let loaded = vars.clone();
+        let lookup = move |name: &str| {
+          loaded.iter().rev().find(|(key, _)| key == name).map(|(_, value)| value.to_owned())
+            .or_else(|| preserve_env.then(|| std::env::var(name).ok()).flatten())
+        };
+
+        match dotenv::parse(&contents, &lookup) {
+          Ok(parsed) => vars.extend(parsed),
+          Err(err) => CONSOLE.exit(format!("Invalid env file <brightmagenta>{}</brightmagenta>, {err}", path.display())),
+        }
+      }
+
+      vars
+    }
+
+    /// The log of the run, if the `[log]` section sets a path.
+    fn output_log(log: LaunchConfigLog) -> Option<OutputLog> {
+      let path = log.path?;
+
+      Some(OutputLog {
+        path: PathBuf::from(path),
+        append: log.append.unwrap_or(true),
+        max_size: log.max_size.map(|max_size| max_size.0),
+        keep: log.keep.unwrap_or(5),
+        timestamps: log.timestamps.unwrap_or(false),
+        prefix: log.prefix.unwrap_or(false),
+      })
+    }
+
+    fn process_limits(umask: Option<Umask>, limits: LaunchConfigLimits, scheduling: LaunchConfigScheduling) -> ProcessLimits {
+      let ioprio = match (scheduling.ioprio_class, scheduling.ioprio_level) {
+        (None, None) => None,
+        (_, Some(level)) if level > 7 => CONSOLE.exit(format!("The io priority level must be between 0 and 7, got {level}")),
+        (class, level) => Some((class.unwrap_or(IoPriorityClass::BestEffort), level.unwrap_or(4))),
+      };
+
+      ProcessLimits {
+        nofile: limits.nofile,
+        nproc: limits.nproc,
+        address_space: limits.address_space,
+        cpu: limits.cpu,
+        core: limits.core,
+        hard: limits.hard.unwrap_or(false),
+        nice: scheduling.nice,
+        ioprio,
+        cpu_affinity: scheduling.cpu_affinity,
+        umask,
+      }
+    }
+  }
+
+  mod restarts {
+    /// Whether the command should be started again after exiting with `status`.
+    pub fn should_restart(self: &Self, status: &ExitStatus, restarts: u32) -> bool {
+      let policy_allows = match self.restart {
+        RestartPolicy::No => false,
+        RestartPolicy::OnFailure => !status.success(),
+        RestartPolicy::Always => true,
+      };
+
+      policy_allows && self.max_restarts.is_none_or(|max| restarts < max)
+    }
+
+    /// Exponential backoff with equal jitter: half of the delay is fixed, the other half is random.
+    pub fn restart_backoff(self: &Self, attempt: u32) -> Duration {
+      let exponential = self.restart_delay.saturating_mul(2_u32.saturating_pow(attempt.saturating_sub(1)));
+      let delay = exponential.min(self.restart_max_delay);
+
+      let half = delay.checked_div(2).unwrap_or_default();
+      let half_millis = u64::try_from(half.as_millis()).unwrap_or(u64::MAX);
+      let jitter = std::collections::hash_map::RandomState::new().hash_one(attempt).checked_rem(half_millis.saturating_add(1)).unwrap_or_default();
+
+      half.saturating_add(Duration::from_millis(jitter))
+    }
+  }
+
+  mod implementation {
+    /// Renders the preset environment into plain strings.
+    /// Arrays are joined with `:`, so PATH-like variables can be written as lists.
+    /// Variables from `env_files` come first, so that the inline table overrides them.
+    pub fn environment_vars(self: &Self) -> Vec<(String, String)> {
+      let mut vars = self.environment.iter()
+        .map(|(key, value)| (key.to_owned(), Self::render_value(key, value)))
+        .collect::<Vec<(String, String)>>();
+
+      vars.sort_by(|a, b| a.0.cmp(&b.0));
+
+      let mut env_file_vars = self.env_file_vars.iter()
+        .filter(|(key, _)| !self.environment.contains_key(key))
+        .cloned()
+        .collect::<Vec<(String, String)>>();
+      env_file_vars.extend(vars);
+      env_file_vars
+    }
+
+    fn render_value(key: &str, value: &Value) -> String {
+      match value {
+        Value::String(string) => string.to_owned(),
+        Value::Integer(integer) => integer.to_string(),
+        Value::Float(float) => float.to_string(),
+        Value::Boolean(boolean) => boolean.to_string(),
+        Value::Datetime(datetime) => datetime.to_string(),
+        Value::Array(values) => values.iter().map(|value| Self::render_value(key, value)).collect::<Vec<String>>().join(":"),
+        Value::Table(_) => CONSOLE.exit(format!("The environment variable <brightmagenta>{key}</brightmagenta> cannot be a table")),
       }
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::{LaunchCommand, LaunchConfig};
+
+  /// Reads and expands a preset with the given contents, next to an env file that sets `FROM_FILE`.
+  fn resolve(name: &str, contents: &str) -> Result<LaunchConfig, String> {
+    let dir = std::env::temp_dir().join(format!("ctr-test-{name}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap_or_default();
+    std::fs::write(dir.join("test.env"), "FROM_FILE=file\n").unwrap_or_default();
+
+    let path = dir.join(format!("{name}.toml"));
+    std::fs::write(&path, format!("env_files = [\"{}\"]\n{contents}", dir.join("test.env").display())).unwrap_or_default();
+
+    let config = LaunchConfig::from_file(&path).map_err(|err| err.to_string()).and_then(|mut config| config.interpolate(&path).map(|()| config));
+    std::fs::remove_dir_all(&dir).unwrap_or_default();
+    config
+  }
+
+  #[test]
+  fn shell_command_uses_the_preset_environment() {
+    let config = resolve("shell", "[general]\ncommand = 'echo ${FOO} ${FROM_FILE} $FOO pid=$$; for f in a; do echo \"${f}\"; done'\n[environment]\nFOO = \"bar\"\n");
+
+    match config.map(|resolved| resolved.general.command) {
+      Ok(Some(LaunchCommand::Shell(script))) => assert_eq!(script, "echo bar file $FOO pid=$$; for f in a; do echo \"${f}\"; done"),
+      other => panic!("expected a shell command, got {other:?}"),
+    }
+  }
+
+  #[test]
+  fn exec_command_expands_like_a_value() {
+    let config = resolve("exec", "[general]\ncommand = [\"echo\", \"${X}\", \"${UNSET_IN_TEST:-default}\", \"$$X\"]\n[environment]\nX = \"set\"\n");
+
+    match config.map(|resolved| resolved.general.command) {
+      Ok(Some(LaunchCommand::Exec(argv))) => assert_eq!(argv, ["echo", "set", "default", "$X"]),
+      other => panic!("expected an argv, got {other:?}"),
+    }
+  }
+
+  #[test]
+  fn exec_command_fails_on_undefined_variables() {
+    let err = resolve("exec-undefined", "[general]\ncommand = [\"echo\", \"${UNSET_IN_TEST}\"]\n").err().unwrap_or_default();
+    assert!(err.contains("general.command") && err.contains("exec-undefined.toml"), "unexpected error: {err}");
+  }
+}
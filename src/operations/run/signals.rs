@@ -0,0 +1,280 @@
+use std::{
+  fmt::{Display, Formatter},
+  os::unix::process::{CommandExt, ExitStatusExt},
+  process::{Child, Command, ExitStatus},
+  str::FromStr,
+  sync::atomic::{AtomicBool, AtomicI32, Ordering},
+  thread::sleep,
+  time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Signals that are passed on to the process group of the command as they are.
+const FORWARDED: [libc::c_int; 5] = [libc::SIGINT, libc::SIGQUIT, libc::SIGWINCH, libc::SIGUSR1, libc::SIGUSR2];
+/// Signals that stop the command with its stop signal, followed by SIGKILL after the grace period.
+const STOPPING: [libc::c_int; 2] = [libc::SIGTERM, libc::SIGHUP];
+
+const NAMES: [(&str, libc::c_int); 15] = [
+  ("HUP", libc::SIGHUP),
+  ("INT", libc::SIGINT),
+  ("QUIT", libc::SIGQUIT),
+  ("KILL", libc::SIGKILL),
+  ("USR1", libc::SIGUSR1),
+  ("USR2", libc::SIGUSR2),
+  ("PIPE", libc::SIGPIPE),
+  ("ALRM", libc::SIGALRM),
+  ("TERM", libc::SIGTERM),
+  ("CONT", libc::SIGCONT),
+  ("STOP", libc::SIGSTOP),
+  ("TSTP", libc::SIGTSTP),
+  ("TTIN", libc::SIGTTIN),
+  ("TTOU", libc::SIGTTOU),
+  ("WINCH", libc::SIGWINCH),
+];
+
+/// The process group of the running command, 0 if there is none.
+static CHILD_GROUP: AtomicI32 = AtomicI32::new(0);
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+/// Whether ctr received SIGINT since the command was started.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+/// Whether the terminal was handed to the process group of the command.
+static GAVE_TERMINAL: AtomicBool = AtomicBool::new(false);
+
+/// A signal, written as `SIGTERM`, `TERM` or `15`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "RawSignal", into = "String")]
+pub struct Signal(pub libc::c_int);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawSignal {
+  Number(libc::c_int),
+  Name(String),
+}
+
+impl Default for Signal {
+  fn default() -> Self {
+    Self(libc::SIGTERM)
+  }
+}
+
+impl TryFrom<RawSignal> for Signal {
+  type Error = String;
+
+  fn try_from(raw: RawSignal) -> Result<Self, Self::Error> {
+    match raw {
+      RawSignal::Number(number) => number.to_string().parse(),
+      RawSignal::Name(name) => name.parse(),
+    }
+  }
+}
+
+impl FromStr for Signal {
+  type Err = String;
+
+  fn from_str(input: &str) -> Result<Self, Self::Err> {
+    let text = input.trim().to_uppercase();
+    let name = text.strip_prefix("SIG").unwrap_or(&text);
+
+    let number = match name.parse::<libc::c_int>() {
+      Ok(parsed) => NAMES.iter().any(|(_, known)| *known == parsed).then_some(parsed),
+      Err(_) => NAMES.iter().find(|(known, _)| *known == name).map(|(_, known)| *known),
+    };
+
+    number.map(Self).ok_or_else(|| format!("`{input}` is not a valid signal, expected something like SIGTERM or 15"))
+  }
+}
+
+impl Display for Signal {
+  fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+    match NAMES.iter().find(|(_, number)| *number == self.0) {
+      Some((name, _)) => write!(f, "SIG{name}"),
+      None => write!(f, "{}", self.0),
+    }
+  }
+}
+
+impl From<Signal> for String {
+  fn from(signal: Signal) -> Self {
+    signal.to_string()
+  }
+}
+
+extern "C" fn on_signal(signal: libc::c_int) {
+  if STOPPING.contains(&signal) {
+    STOP_REQUESTED.store(true, Ordering::SeqCst);
+    return;
+  }
+
+  if signal == libc::SIGINT {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+  }
+
+  let group = CHILD_GROUP.load(Ordering::SeqCst);
+  if group > 0_i32 {
+    // SAFETY: `kill` is async-signal-safe
+    unsafe {
+      libc::kill(group.saturating_neg(), signal);
+    }
+  }
+}
+
+/// Passes the signals ctr receives on to the process group of `child`, which has to lead its own group.
+/// SIGTERM and SIGHUP are not passed on, they only mark the command to be stopped, see [`stop_requested`].
+pub fn forward_signals(child: &Child) {
+  CHILD_GROUP.store(i32::try_from(child.id()).unwrap_or(0_i32), Ordering::SeqCst);
+  INTERRUPTED.store(false, Ordering::SeqCst);
+
+  let handler: extern "C" fn(libc::c_int) = on_signal;
+  for signal in FORWARDED.iter().chain(STOPPING.iter()) {
+    // SAFETY: the handler only stores to atomics and calls `kill`
+    #[allow(clippy::as_conversions, clippy::fn_to_numeric_cast_any)]
+    unsafe {
+      libc::signal(*signal, handler as libc::sighandler_t);
+    }
+  }
+}
+
+/// Whether ctr received SIGTERM or SIGHUP while the command was running.
+pub fn stop_requested() -> bool {
+  STOP_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Stops passing signals on, once the command was reaped and its process group id may be reused.
+pub fn forget_child() {
+  CHILD_GROUP.store(0_i32, Ordering::SeqCst);
+}
+
+/// Whether the command was interrupted with Ctrl-C or SIGINT, which stops ctr instead of restarting the command.
+pub fn interrupted(status: ExitStatus) -> bool {
+  INTERRUPTED.load(Ordering::SeqCst) || exit_code(status) == libc::SIGINT.saturating_add(128)
+}
+
+/// Sleeps for `duration`, unless ctr is asked to stop or interrupted meanwhile. Returns whether it slept for the whole time.
+pub fn sleep_unless_stopped(duration: Duration, poll: Duration) -> bool {
+  let until = Instant::now().checked_add(duration).unwrap_or_else(Instant::now);
+  loop {
+    if stop_requested() || INTERRUPTED.load(Ordering::SeqCst) {
+      return false;
+    }
+
+    let now = Instant::now();
+    if now >= until {
+      return true;
+    }
+
+    sleep(poll.min(until.saturating_duration_since(now)));
+  }
+}
+
+/// Sends `signal` to the process group of `child`.
+pub fn signal_group(child: &Child, signal: libc::c_int) {
+  if let Some(group) = i32::try_from(child.id()).ok().and_then(i32::checked_neg) {
+    // SAFETY: `kill` has no memory safety requirements
+    unsafe {
+      libc::kill(group, signal);
+    }
+  }
+}
+
+/// The exit code ctr reports for `status`, 128 + the signal if the command was killed by one, like shells do.
+pub fn exit_code(status: ExitStatus) -> i32 {
+  status.code()
+    .or_else(|| status.signal().map(|signal| signal.saturating_add(128)))
+    .unwrap_or(1_i32)
+}
+
+/// Whether the terminal on stdin belongs to the process group of ctr.
+fn owns_terminal() -> bool {
+  // SAFETY: these calls have no memory safety requirements
+  unsafe { libc::isatty(libc::STDIN_FILENO) == 1_i32 && libc::tcgetpgrp(libc::STDIN_FILENO) == libc::getpgrp() }
+}
+
+/// Makes the process group of `pgrp` the foreground group of the terminal.
+/// SIGTTOU is ignored meanwhile, since the caller may be in the background group.
+fn set_foreground(pgrp: libc::pid_t) {
+  // SAFETY: these calls are async-signal-safe and have no memory safety requirements
+  unsafe {
+    let previous = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+    libc::tcsetpgrp(libc::STDIN_FILENO, pgrp);
+    libc::signal(libc::SIGTTOU, previous);
+  }
+}
+
+/// Starts `command` in its own process group, so that ctr can signal everything it starts.
+/// If ctr owns the terminal, the new group becomes its foreground group, so the command can still
+/// read from it and receives Ctrl-C and Ctrl-Z itself. Both the child and ctr do this, since either may run first.
+pub fn own_process_group(command: &mut Command) {
+  command.process_group(0);
+  if !owns_terminal() {
+    return;
+  }
+
+  GAVE_TERMINAL.store(true, Ordering::SeqCst);
+
+  // SAFETY: the closure only performs async-signal-safe syscalls
+  unsafe {
+    command.pre_exec(|| {
+      set_foreground(libc::getpgrp());
+      Ok(())
+    });
+  }
+}
+
+/// Hands the terminal to the process group of `child` after it was spawned, see [`own_process_group`].
+pub fn give_terminal(child: &Child) {
+  if GAVE_TERMINAL.load(Ordering::SeqCst) {
+    set_foreground(libc::pid_t::try_from(child.id()).unwrap_or(0_i32));
+  }
+}
+
+/// Makes the process group of ctr the foreground group again.
+pub fn take_terminal() {
+  if GAVE_TERMINAL.swap(false, Ordering::SeqCst) {
+    // SAFETY: `getpgrp` has no memory safety requirements
+    set_foreground(unsafe { libc::getpgrp() });
+  }
+}
+
+/// Checks whether `child` exited, without blocking.
+/// If the command was suspended with Ctrl-Z, ctr suspends itself as well, so that the shell regains control,
+/// and resumes the command once ctr is resumed.
+pub fn try_wait(child: &mut Child) -> std::io::Result<Option<ExitStatus>> {
+  let pid = libc::pid_t::try_from(child.id()).map_err(std::io::Error::other)?;
+  let mut status: libc::c_int = 0;
+
+  // SAFETY: `status` is valid for writes
+  let waited = unsafe { libc::waitpid(pid, &mut status, libc::WNOHANG | libc::WUNTRACED) };
+  match waited {
+    -1 => child.try_wait().inspect(|reaped| if reaped.is_some() { forget_child() }),
+    0 => Ok(None),
+    _ if libc::WIFSTOPPED(status) => {
+      let had_terminal = GAVE_TERMINAL.load(Ordering::SeqCst);
+      let was_raw = super::pty::restore_mode();
+      take_terminal();
+
+      // SAFETY: these calls have no memory safety requirements
+      unsafe {
+        libc::raise(libc::SIGTSTP);
+      }
+
+      // resumed with `fg` or `bg`
+      if had_terminal && owns_terminal() {
+        GAVE_TERMINAL.store(true, Ordering::SeqCst);
+        set_foreground(pid);
+      }
+
+      if was_raw {
+        super::pty::set_raw_mode();
+      }
+
+      signal_group(child, libc::SIGCONT);
+      Ok(None)
+    },
+    _ => {
+      forget_child();
+      Ok(Some(ExitStatus::from_raw(status)))
+    },
+  }
+}
@@ -0,0 +1,476 @@
+use std::{
+  collections::HashMap,
+  ffi::CString,
+  fs,
+  os::{
+    fd::{AsRawFd, FromRawFd, OwnedFd},
+    unix::ffi::OsStrExt,
+  },
+  path::{Path, PathBuf},
+  thread::sleep,
+  time::{Duration, Instant, SystemTime},
+};
+
+use regex::Regex;
+use std_v2::{console::CONSOLE, struct_gen};
+
+/// How often the watched files are scanned if inotify is not available.
+const SCAN_INTERVAL: Duration = Duration::from_millis(500);
+const INOTIFY_MASK: u32 = libc::IN_CLOSE_WRITE | libc::IN_MODIFY | libc::IN_CREATE | libc::IN_DELETE | libc::IN_MOVED_FROM | libc::IN_MOVED_TO;
+/// Size of `inotify_event` without the name that follows it.
+const EVENT_HEADER_SIZE: usize = 16;
+
+/// Converts a glob to a regex that matches whole paths.
+/// `**/` matches any number of directories, `*` and `?` do not match `/`.
+pub fn glob_regex(glob: &str) -> Result<Regex, String> {
+  let mut pattern = String::from("^");
+  let mut chars = glob.chars().peekable();
+
+  while let Some(c) = chars.next() {
+    match c {
+      '*' if chars.next_if_eq(&'*').is_some() => {
+        pattern.push_str(if chars.next_if_eq(&'/').is_some() { "(?:.*/)?" } else { ".*" });
+      },
+      '*' => pattern.push_str("[^/]*"),
+      '?' => pattern.push_str("[^/]"),
+      '[' => {
+        pattern.push('[');
+        if chars.next_if_eq(&'!').is_some() {
+          pattern.push('^');
+        }
+
+        for class_char in chars.by_ref() {
+          if class_char == ']' {
+            break;
+          }
+
+          if matches!(class_char, '\\' | '[') {
+            pattern.push('\\');
+          }
+          pattern.push(class_char);
+        }
+
+        pattern.push(']');
+      },
+      _ => pattern.push_str(&regex::escape(c.encode_utf8(&mut [0_u8; 4]))),
+    }
+  }
+
+  pattern.push('$');
+  Regex::new(&pattern).map_err(|err| format!("`{glob}` is not a valid glob: {err}"))
+}
+
+fn is_glob(path: &str) -> bool {
+  path.contains(['*', '?', '['])
+}
+
+/// How changes are noticed. inotify is preferred, scanning is the fallback if it is unavailable or out of watches.
+#[derive(Debug)]
+enum Backend {
+  Inotify { fd: OwnedFd, dirs: HashMap<i32, PathBuf> },
+  Scan { files: HashMap<PathBuf, (SystemTime, u64)>, last_scan: Instant },
+}
+
+struct_gen! {
+  /// A path from `watch.paths`. Globs are watched from the directory before the first wildcard.
+  pub struct WatchTarget use Clone {
+    pub let root: PathBuf = PathBuf::new();
+    pub let recursive: bool = true;
+    pub let pattern: Option<Regex> = None;
+  }
+
+  mod constructors {
+    /// Fails if the path, or the directory a glob starts in, does not exist, since it could not be watched.
+    pub fn parse(path: &str, base: &Path) -> Result<Self, String> {
+      if !is_glob(path) {
+        let root = base.join(path);
+        if !root.exists() {
+          return Err(format!("`{path}` does not exist"));
+        }
+
+        let recursive = !root.is_file();
+        return Ok(Self { root, recursive, pattern: None });
+      }
+
+      let absolute = base.join(path);
+      let root = absolute.ancestors()
+        .find(|ancestor| !is_glob(&ancestor.to_string_lossy()))
+        .map_or_else(|| base.to_path_buf(), Path::to_path_buf);
+
+      if !root.is_dir() {
+        return Err(format!("`{path}` starts in `{}`, which does not exist", root.display()));
+      }
+
+      Ok(Self { root, recursive: true, pattern: Some(glob_regex(&absolute.to_string_lossy())?) })
+    }
+  }
+
+  mod implementation {
+    pub fn matches(self: &Self, path: &Path) -> bool {
+      match self.pattern {
+        Some(ref pattern) => pattern.is_match(&path.to_string_lossy()),
+        None if self.recursive => path.starts_with(&self.root),
+        None => path == self.root,
+      }
+    }
+
+    /// The directory that has to be watched for this target.
+    pub fn dir(self: &Self) -> PathBuf {
+      if self.recursive {
+        self.root.clone()
+      } else {
+        self.root.parent().map_or_else(PathBuf::new, Path::to_path_buf)
+      }
+    }
+  }
+}
+
+struct_gen! {
+  /// A `.gitignore` pattern, or one from `watch.ignore`, relative to `base`.
+  pub struct IgnoreRule use Clone {
+    pub let base: PathBuf = PathBuf::new();
+    pub let pattern: Option<Regex> = None;
+    pub let negated: bool = false;
+    pub let dir_only: bool = false;
+  }
+
+  mod constructors {
+    /// Parses a line of a `.gitignore`, `None` for blank lines and comments.
+    pub fn parse(line: &str, base: &Path) -> Option<Result<Self, String>> {
+      let trimmed = line.trim_end();
+      if trimmed.is_empty() || trimmed.starts_with('#') {
+        return None;
+      }
+
+      let negated = trimmed.starts_with('!');
+      let unnegated = trimmed.trim_start_matches('!');
+      let dir_only = unnegated.ends_with('/');
+      let glob = unnegated.trim_end_matches('/');
+
+      // patterns without a slash match at any depth, like `target` or `*.log`
+      let anchored = glob.contains('/');
+      let relative = glob.trim_start_matches('/');
+      let full = if anchored { relative.to_owned() } else { format!("**/{relative}") };
+
+      Some(glob_regex(&full).map(|pattern| Self { base: base.to_path_buf(), pattern: Some(pattern), negated, dir_only }))
+    }
+  }
+
+  mod implementation {
+    /// Whether the rule matches `path` or one of its parent directories below `base`.
+    pub fn matches(self: &Self, path: &Path, is_dir: bool) -> bool {
+      let (Some(ref pattern), Ok(relative)) = (&self.pattern, path.strip_prefix(&self.base)) else {
+        return false;
+      };
+
+      let mut prefix = PathBuf::new();
+      let mut components = relative.components().peekable();
+      while let Some(component) = components.next() {
+        prefix.push(component);
+        let is_last = components.peek().is_none();
+
+        if (!self.dir_only || !is_last || is_dir) && pattern.is_match(&prefix.to_string_lossy()) {
+          return true;
+        }
+      }
+
+      false
+    }
+  }
+}
+
+struct_gen! {
+  /// Watches files for changes, honoring `.gitignore` files and `watch.ignore`.
+  pub struct Watcher {
+    pub let targets: Vec<WatchTarget> = Vec::new();
+    pub let rules: Vec<IgnoreRule> = Vec::new();
+    /// Directories whose `.gitignore` has already been loaded.
+    pub let loaded: Vec<PathBuf> = Vec::new();
+    pub let debounce: Duration = Duration::from_millis(200);
+    let backend: Backend = Backend::Scan { files: HashMap::new(), last_scan: Instant::now() };
+  }
+
+  mod constructors {
+    /// Watches `paths`, relative paths are resolved against `base`.
+    pub fn start(paths: &[String], ignore: &[String], base: &Path, debounce: Duration) -> Result<Self, String> {
+      let mut watcher = Self { debounce, ..Self::default() };
+
+      for path in paths {
+        watcher.targets.push(WatchTarget::parse(path, base)?);
+      }
+
+      for pattern in ignore {
+        if let Some(rule) = IgnoreRule::parse(pattern, base) {
+          watcher.rules.push(rule?);
+        }
+      }
+
+      // `.gitignore` files further up apply as well, up to the root of the repository
+      let roots = watcher.targets.iter().map(WatchTarget::dir).collect::<Vec<PathBuf>>();
+      for root in &roots {
+        let mut ancestors = root.ancestors().skip(1).collect::<Vec<&Path>>();
+        if let Some(repository) = ancestors.iter().position(|ancestor| ancestor.join(".git").exists()) {
+          ancestors.truncate(repository.saturating_add(1));
+        } else {
+          ancestors.clear();
+        }
+
+        for ancestor in ancestors.into_iter().rev() {
+          watcher.load_gitignore(ancestor);
+        }
+      }
+
+      // SAFETY: `inotify_init1` has no memory safety requirements
+      let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+      if fd >= 0_i32 {
+        // SAFETY: the descriptor was just created and is owned by nothing else
+        watcher.backend = Backend::Inotify { fd: unsafe { OwnedFd::from_raw_fd(fd) }, dirs: HashMap::new() };
+      } else {
+        CONSOLE.warn(format!("inotify is not available, scanning for changes instead: {}", std::io::Error::last_os_error()));
+      }
+
+      for target in watcher.targets.clone() {
+        watcher.watch_dir(&target.dir(), target.recursive);
+      }
+
+      if let Backend::Scan { ref mut files, .. } = watcher.backend {
+        *files = Self::snapshot(&mut watcher.rules, &mut watcher.loaded, &watcher.targets);
+      }
+
+      Ok(watcher)
+    }
+  }
+
+  mod ignore {
+    fn load_gitignore(self: &mut Self, dir: &Path) {
+      Self::load_gitignore_into(&mut self.rules, &mut self.loaded, dir);
+    }
+
+    fn load_gitignore_into(rules: &mut Vec<IgnoreRule>, loaded: &mut Vec<PathBuf>, dir: &Path) {
+      if loaded.iter().any(|loaded_dir| loaded_dir == dir) {
+        return;
+      }
+
+      loaded.push(dir.to_path_buf());
+      let Ok(contents) = fs::read_to_string(dir.join(".gitignore")) else {
+        return;
+      };
+
+      for line in contents.lines() {
+        match IgnoreRule::parse(line, dir) {
+          Some(Ok(rule)) => rules.push(rule),
+          Some(Err(err)) => CONSOLE.warn(format!("Ignoring a pattern in {}: {err}", dir.join(".gitignore").display())),
+          None => {},
+        }
+      }
+    }
+
+    /// The last rule that matches decides, like in `.gitignore`. `.git` is always ignored.
+    fn is_ignored(rules: &[IgnoreRule], path: &Path, is_dir: bool) -> bool {
+      if path.components().any(|component| component.as_os_str() == ".git") {
+        return true;
+      }
+
+      rules.iter().rev()
+        .find(|rule| rule.matches(path, is_dir))
+        .is_some_and(|rule| !rule.negated)
+    }
+
+    /// Whether a change of `path` should trigger a rerun.
+    fn is_relevant(self: &Self, path: &Path) -> bool {
+      self.targets.iter().any(|target| target.matches(path)) && !Self::is_ignored(&self.rules, path, false)
+    }
+  }
+
+  mod inotify {
+    /// Adds watches for `dir`, and all directories below it if `recursive` is set.
+    /// Falls back to scanning if the watches run out.
+    fn watch_dir(self: &mut Self, dir: &Path, recursive: bool) {
+      let mut entries = vec![];
+      Self::walk(&mut self.rules, &mut self.loaded, dir, recursive, &mut entries);
+
+      let Backend::Inotify { ref fd, ref mut dirs } = self.backend else {
+        return;
+      };
+
+      let mut out_of_watches = false;
+      for (path, _) in entries.iter().filter(|(_, is_dir)| *is_dir) {
+        if dirs.values().any(|watched| watched == path) {
+          continue;
+        }
+
+        let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+          continue;
+        };
+
+        // SAFETY: `c_path` is a valid C string that outlives the call
+        let wd = unsafe { libc::inotify_add_watch(fd.as_raw_fd(), c_path.as_ptr(), INOTIFY_MASK) };
+        if wd >= 0_i32 {
+          dirs.insert(wd, path.clone());
+          continue;
+        }
+
+        if std::io::Error::last_os_error().raw_os_error() == Some(libc::ENOSPC) {
+          out_of_watches = true;
+          break;
+        }
+      }
+
+      if out_of_watches {
+        CONSOLE.warn("Out of inotify watches, scanning for changes instead");
+        self.backend = Backend::Scan { files: Self::snapshot(&mut self.rules, &mut self.loaded, &self.targets), last_scan: Instant::now() };
+      }
+    }
+
+    /// Reads the pending inotify events, waiting at most `timeout` for the first one.
+    /// Returns the changed files, and the directories that were created or moved in.
+    fn read_events(fd: &OwnedFd, dirs: &HashMap<i32, PathBuf>, timeout: Duration) -> (Vec<PathBuf>, Vec<PathBuf>) {
+      let mut poll_fd = libc::pollfd { fd: fd.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+      let timeout_ms = libc::c_int::try_from(timeout.as_millis()).unwrap_or(libc::c_int::MAX);
+
+      // SAFETY: `poll_fd` is a single valid pollfd
+      if unsafe { libc::poll(&mut poll_fd, 1, timeout_ms) } <= 0_i32 {
+        return (vec![], vec![]);
+      }
+
+      let mut buffer = [0_u8; 8192];
+      // SAFETY: the buffer is valid for writes of its whole length
+      let read = unsafe { libc::read(fd.as_raw_fd(), buffer.as_mut_ptr().cast(), buffer.len()) };
+      let length = usize::try_from(read).unwrap_or(0);
+
+      let mut files = vec![];
+      let mut created_dirs = vec![];
+      let mut offset: usize = 0;
+      while let Some(header) = buffer.get(offset..offset.saturating_add(EVENT_HEADER_SIZE)).filter(|_| offset < length) {
+        let field = |start: usize| header.get(start..start.saturating_add(4)).and_then(|bytes| <[u8; 4]>::try_from(bytes).ok()).unwrap_or_default();
+        let wd = i32::from_ne_bytes(field(0));
+        let mask = u32::from_ne_bytes(field(4));
+        let name_length = usize::try_from(u32::from_ne_bytes(field(12))).unwrap_or(0);
+
+        let name_start = offset.saturating_add(EVENT_HEADER_SIZE);
+        let padded_name = buffer.get(name_start..name_start.saturating_add(name_length)).unwrap_or_default();
+        let name = padded_name.split(|byte| *byte == 0).next().unwrap_or_default();
+        offset = name_start.saturating_add(name_length);
+
+        let Some(dir) = dirs.get(&wd) else {
+          continue;
+        };
+
+        let path = dir.join(std::ffi::OsStr::from_bytes(name));
+        if mask & libc::IN_ISDIR == 0 {
+          files.push(path);
+        } else if mask & (libc::IN_CREATE | libc::IN_MOVED_TO) != 0 {
+          created_dirs.push(path);
+        } else {
+          // removed directories lose their watch on their own
+        }
+      }
+
+      (files, created_dirs)
+    }
+  }
+
+  mod scan {
+    /// Collects `dir` and the entries below it that are not ignored, loading `.gitignore` files on the way.
+    fn walk(rules: &mut Vec<IgnoreRule>, loaded: &mut Vec<PathBuf>, dir: &Path, recursive: bool, entries: &mut Vec<(PathBuf, bool)>) {
+      Self::load_gitignore_into(rules, loaded, dir);
+      entries.push((dir.to_path_buf(), true));
+
+      let Ok(read_dir) = fs::read_dir(dir) else {
+        return;
+      };
+
+      for entry in read_dir.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+
+        if Self::is_ignored(rules, &path, is_dir) {
+          continue;
+        }
+
+        if !is_dir {
+          entries.push((path, false));
+        } else if recursive {
+          Self::walk(rules, loaded, &path, true, entries);
+        } else {
+          // only the files directly inside are watched
+        }
+      }
+    }
+
+    /// Modification time and size of every watched file.
+    fn snapshot(rules: &mut Vec<IgnoreRule>, loaded: &mut Vec<PathBuf>, targets: &[WatchTarget]) -> HashMap<PathBuf, (SystemTime, u64)> {
+      let mut entries = vec![];
+      for target in targets {
+        Self::walk(rules, loaded, &target.dir(), target.recursive, &mut entries);
+      }
+
+      entries.into_iter()
+        .filter(|(path, is_dir)| !is_dir && targets.iter().any(|target| target.matches(path)))
+        .filter_map(|(path, _)| {
+          let metadata = fs::metadata(&path).ok()?;
+          Some((path, (metadata.modified().ok()?, metadata.len())))
+        })
+        .collect()
+    }
+  }
+
+  mod implementation {
+    /// Waits at most `timeout` for changed files.
+    fn changes(self: &mut Self, timeout: Duration) -> Vec<PathBuf> {
+      let (mut changed, created_dirs) = match self.backend {
+        Backend::Inotify { ref fd, ref dirs } => Self::read_events(fd, dirs, timeout),
+        Backend::Scan { .. } => (self.scan_changes(timeout), vec![]),
+      };
+
+      for dir in created_dirs {
+        if !Self::is_ignored(&self.rules, &dir, true) {
+          let recursive = self.targets.iter().any(|target| target.recursive && dir.starts_with(&target.root));
+          self.watch_dir(&dir, recursive);
+        }
+      }
+
+      changed.retain(|path| self.is_relevant(path));
+      changed
+    }
+
+    /// Compares the watched files against the last scan, at most every [`SCAN_INTERVAL`].
+    fn scan_changes(self: &mut Self, timeout: Duration) -> Vec<PathBuf> {
+      let Backend::Scan { ref mut files, ref mut last_scan } = self.backend else {
+        return vec![];
+      };
+
+      let next_scan = last_scan.checked_add(SCAN_INTERVAL).unwrap_or_else(Instant::now);
+      let now = Instant::now();
+      if now < next_scan {
+        sleep(timeout.min(next_scan.saturating_duration_since(now)));
+        return vec![];
+      }
+
+      let current = Self::snapshot(&mut self.rules, &mut self.loaded, &self.targets);
+      let mut differences = current.iter()
+        .filter(|(path, state)| files.get(*path) != Some(*state))
+        .map(|(path, _)| path.clone())
+        .chain(files.keys().filter(|path| !current.contains_key(*path)).cloned())
+        .collect::<Vec<PathBuf>>();
+      differences.sort();
+
+      *files = current;
+      *last_scan = Instant::now();
+      differences
+    }
+
+    /// Waits at most `timeout` for a change. Once there is one, further changes are awaited until
+    /// none arrived for `debounce`, so that a save of many files only reruns once.
+    /// Returns the file that changed first.
+    pub fn next_change(self: &mut Self, timeout: Duration) -> Option<PathBuf> {
+      let first = self.changes(timeout).into_iter().next()?;
+
+      // a command that keeps writing into a watched directory must not delay the rerun forever
+      let settle_by = Instant::now().checked_add(self.debounce.saturating_mul(10)).unwrap_or_else(Instant::now);
+      while Instant::now() < settle_by && !self.changes(self.debounce).is_empty() {}
+
+      Some(first)
+    }
+  }
+}
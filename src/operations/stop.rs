@@ -0,0 +1,37 @@
+use clap::Args;
+use std_v2::{command::Operation, console::CONSOLE, struct_gen};
+
+use crate::operations::run::{duration::HumanDuration, job::Job};
+
+struct_gen! {
+  #[usage(Flags, Operand { name: "job".to_string() })]
+  pub struct Options use Args, std_v2::derive::Command {
+    #[arg(short = 'H', long), help]
+    let help: bool = false;
+
+    #[arg(short, long), flag("Time to wait after the stop signal before sending SIGKILL", example = "10s")]
+    let timeout: Option<HumanDuration> = None;
+
+    #[arg()]
+    let job: Option<String> = None;
+  }
+
+  impl Operation {
+    const NAME: &'static str = "stop";
+
+    fn main(self: &Self) -> std::io::Result<()> {
+      (self.help).then(|| Self::usage(0));
+
+      let job = Job::find(&self.job);
+      if !job.is_alive() {
+        job.remove();
+        CONSOLE.exit(format!("<brightmagenta>{}</brightmagenta> is not running", job.id));
+      }
+
+      job.stop(self.timeout.map(|timeout| timeout.0));
+      CONSOLE.print(format!("Stopped job <brightmagenta>{}</brightmagenta>", job.id));
+
+      Ok(())
+    }
+  }
+}